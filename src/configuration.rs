@@ -1,84 +1,635 @@
-use config::{Config, File};
 use lazy_static::lazy_static;
 use serde::Deserialize;
-use std::sync::RwLock;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::{fs, io};
 
-#[derive(Debug, Deserialize)]
+use crate::types::{Code, DaemonType};
+
+/// Settings file read unless another one is given
+pub(crate) const SETTINGS_FILE: &str = "config/settings.json";
+
+/// Key of the notes in the settings file and its sections, not read as a setting
+const COMMENTS_KEY: &str = "$comments";
+
+/// Rectangle bounds in pixels of the reference resolution
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RectCfg {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ResolutionCfg {
+    pub width: i32,
+    pub height: i32,
+}
+
+// Sections with an area list its bounds next to their other settings. They are not flattened
+// from a `RectCfg`, since serde cannot deny the unknown fields of flattened structs.
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BufferCfg {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+    /// Distance between the buffer slots
+    pub slot_width: i32,
+}
+
+impl BufferCfg {
+    pub(crate) fn area(&self) -> RectCfg {
+        RectCfg {
+            left: self.left,
+            right: self.right,
+            top: self.top,
+            bottom: self.bottom,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub(crate) struct DaemonCfg {
-    pub max_length: i32,
     pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+    /// Shortest daemon sequence, shorter rows are not daemons
+    #[serde(default = "default_min_length")]
+    pub min_length: usize,
     pub cell_width: i32,
+    pub row_height: i32,
+    /// Optional row positions, used when no row is detected in the panel
+    #[serde(default)]
     pub rows: Vec<DaemonRow>,
 }
 
-#[derive(Debug, Deserialize)]
+impl DaemonCfg {
+    pub(crate) fn area(&self) -> RectCfg {
+        RectCfg {
+            left: self.left,
+            right: self.right,
+            top: self.top,
+            bottom: self.bottom,
+        }
+    }
+}
+
+fn default_min_length() -> usize {
+    2
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub(crate) struct DaemonRow {
     pub top: i32,
     pub bottom: i32,
 }
 
-// #[derive(Debug, Deserialize)]
-// pub(crate) struct Rect {
-//     left: i32,
-//     right: i32,
-//     top: i32,
-//     bottom: i32,
-// }
-
-// #[derive(Debug, Deserialize)]
-// pub(crate) struct OpenCV {
-//     height_border: i32,
-//     filter_threshold: i32,
-// }
-
-// #[derive(Debug, Deserialize)]
-// pub(crate) struct Settings {
-//     pub buffer: Rect,
-//     pub daemons: Daemon,
-//     pub grid: Rect,
-//     pub valid_codes: Vec<String>,
-//     pub opencv: OpenCV,
-// }
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DaemonNamesCfg {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+    #[serde(default)]
+    pub types: Vec<DaemonNameCfg>,
+}
 
-lazy_static! {
-    static ref SETTINGS: RwLock<Config> = RwLock::new(load());
+impl DaemonNamesCfg {
+    pub(crate) fn area(&self) -> RectCfg {
+        RectCfg {
+            left: self.left,
+            right: self.right,
+            top: self.top,
+            bottom: self.bottom,
+        }
+    }
+}
+
+/// Names shown in game for a daemon type, in any language
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DaemonNameCfg {
+    #[serde(rename = "type")]
+    pub daemon_type: String,
+    pub names: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LayoutCfg {
+    /// Place the rectangles relative to the code matrix header found on screen
+    #[serde(default = "default_auto_layout")]
+    pub auto: bool,
+    /// Header position matching the rectangles of the settings
+    pub matrix_header: RectCfg,
+}
+
+fn default_auto_layout() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct CaptureCfg {
+    /// Index of the captured monitor, 0 is the primary one
+    pub monitor: usize,
+    /// Captured region of the monitor as [x, y, width, height], empty for the whole monitor
+    pub region: Vec<i32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct WatchCfg {
+    pub poll_interval_ms: u64,
+    pub retry_interval_ms: u64,
+    /// Smallest grey level difference of a panel block counted as a change
+    pub change_level: u8,
+}
+
+impl Default for WatchCfg {
+    fn default() -> Self {
+        WatchCfg {
+            poll_interval_ms: 500,
+            retry_interval_ms: 5000,
+            change_level: 12,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ChecksCfg {
+    pub min_buffer_size: u32,
+    pub max_buffer_size: u32,
+    pub min_grid_size: u32,
+    pub max_grid_size: u32,
+    /// Valid sizes of non square grids, as [rows, columns]
+    pub grid_sizes: Vec<[u32; 2]>,
+}
+
+impl Default for ChecksCfg {
+    fn default() -> Self {
+        ChecksCfg {
+            min_buffer_size: 4,
+            max_buffer_size: 8,
+            min_grid_size: 5,
+            max_grid_size: 7,
+            grid_sizes: Vec::new(),
+        }
+    }
+}
+
+/// Engine reading the codes of the cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum OcrEngine {
+    Tesseract,
+    /// Matches the cells against the glyph images in `assets/templates`
+    Templates,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct OpenCvCfg {
+    pub ocr_engine: OcrEngine,
+    /// Engines reading the cells in parallel, 0 for one per CPU
+    pub ocr_threads: usize,
+    pub detect_grid_threshold: f64,
+    pub detect_daemon_threshold: f64,
+    pub buffer_match_threshold: f64,
+    pub ocr_height_border: i32,
+    pub ocr_min_confidence: u8,
+    pub detect_active_line_delta: f64,
+    pub read_daemon_names: bool,
 }
-fn load() -> Config {
-    let mut settings: Config = Config::default();
-    // Add in `./settings.json`
-    settings
-        .merge(File::with_name("config/settings.json"))
-        .unwrap();
 
-    settings
+impl Default for OpenCvCfg {
+    fn default() -> Self {
+        OpenCvCfg {
+            ocr_engine: OcrEngine::Tesseract,
+            ocr_threads: 0,
+            detect_grid_threshold: 0.13,
+            detect_daemon_threshold: 0.11,
+            buffer_match_threshold: 0.7,
+            ocr_height_border: 10,
+            ocr_min_confidence: 70,
+            detect_active_line_delta: 0.07,
+            read_daemon_names: true,
+        }
+    }
 }
 
-pub fn cfg_i32(key: &str) -> i32 {
-    let settings = SETTINGS.read().unwrap();
-    (*settings).get::<i32>(key).unwrap()
+/// Settings file contents. The panel rectangles, valid codes and reference resolution are
+/// required, the other sections default to the values of the shipped settings.
+/// Unknown keys are rejected, except the `$comments` notes of each section.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Settings {
+    pub reference_resolution: ResolutionCfg,
+    pub buffer: BufferCfg,
+    pub daemons: DaemonCfg,
+    pub daemon_names: DaemonNamesCfg,
+    pub grid: RectCfg,
+    pub layout: LayoutCfg,
+    #[serde(default)]
+    pub capture: CaptureCfg,
+    #[serde(default)]
+    pub watch: WatchCfg,
+    pub valid_codes: Vec<Code>,
+    #[serde(default)]
+    pub checks: ChecksCfg,
+    #[serde(default)]
+    pub opencv: OpenCvCfg,
 }
-pub fn cfg_f32(key: &str) -> f32 {
-    let settings = SETTINGS.read().unwrap();
-    (*settings).get::<f32>(key).unwrap()
+
+/// Invalid or unreadable settings file, with the offending key when the file could be parsed
+#[derive(Debug)]
+pub(crate) struct SettingsError {
+    pub file: PathBuf,
+    pub key: Option<String>,
+    pub message: String,
 }
-pub fn cfg_f64(key: &str) -> f64 {
-    let settings = SETTINGS.read().unwrap();
-    (*settings).get::<f64>(key).unwrap()
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "{}: {}: {}", self.file.display(), key, self.message),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
+    }
 }
-pub fn cfg_str(key: &str) -> &str {
-    let settings = SETTINGS.read().unwrap();
-    (*settings).get::<&str>(key).unwrap()
+
+impl std::error::Error for SettingsError {}
+
+/// Setting with a value out of its valid range
+struct Invalid {
+    key: String,
+    message: String,
 }
-pub fn cfg_str_vec(key: &str) -> Vec<String> {
-    let settings = SETTINGS.read().unwrap();
-    (*settings)
-        .get_array(key)
+
+impl Invalid {
+    fn new(key: impl Into<String>, message: String) -> Invalid {
+        Invalid {
+            key: key.into(),
+            message,
+        }
+    }
+}
+
+fn check_at_least<T: PartialOrd + fmt::Display>(
+    key: &str,
+    value: T,
+    min: T,
+) -> Result<(), Invalid> {
+    if value < min {
+        return Err(Invalid::new(
+            key,
+            format!("must be at least {}, found {}", min, value),
+        ));
+    }
+    Ok(())
+}
+
+fn check_range<T: PartialOrd + fmt::Display>(
+    key: &str,
+    value: T,
+    range: RangeInclusive<T>,
+) -> Result<(), Invalid> {
+    if !range.contains(&value) {
+        return Err(Invalid::new(
+            key,
+            format!(
+                "must be from {} to {}, found {}",
+                range.start(),
+                range.end(),
+                value
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Check that the bounds are in order, in the positive quadrant
+fn check_rect(key: &str, rect: &RectCfg) -> Result<(), Invalid> {
+    check_at_least(&format!("{}.left", key), rect.left, 0)?;
+    check_at_least(&format!("{}.top", key), rect.top, 0)?;
+    if rect.left >= rect.right {
+        return Err(Invalid::new(
+            format!("{}.left", key),
+            format!(
+                "must be less than {}.right, found {} and {}",
+                key, rect.left, rect.right
+            ),
+        ));
+    }
+    if rect.top >= rect.bottom {
+        return Err(Invalid::new(
+            format!("{}.top", key),
+            format!(
+                "must be less than {}.bottom, found {} and {}",
+                key, rect.top, rect.bottom
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Check that a minimum setting is not above its maximum
+fn check_order(min_key: &str, min: u32, max_key: &str, max: u32) -> Result<(), Invalid> {
+    if min > max {
+        return Err(Invalid::new(
+            min_key,
+            format!("must not be above {}, found {} and {}", max_key, min, max),
+        ));
+    }
+    Ok(())
+}
+
+impl Settings {
+    /// Read and validate a settings file
+    pub(crate) fn read(path: &Path) -> Result<Settings, SettingsError> {
+        let error = |key: Option<String>, message: String| SettingsError {
+            file: path.to_path_buf(),
+            key,
+            message,
+        };
+        let text = fs::read_to_string(path).map_err(|e| error(None, e.to_string()))?;
+        let mut value: Value =
+            serde_json::from_str(&text).map_err(|e| error(None, e.to_string()))?;
+        remove_comments(&mut value);
+        let settings: Settings = serde_path_to_error::deserialize(value).map_err(|e| {
+            // The path of an error at the top level is "."
+            let key = Some(e.path().to_string()).filter(|key| key != ".");
+            error(key, e.inner().to_string())
+        })?;
+        settings
+            .validate()
+            .map_err(|invalid| error(Some(invalid.key), invalid.message))?;
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<(), Invalid> {
+        let resolution = &self.reference_resolution;
+        check_at_least("reference_resolution.width", resolution.width, 1)?;
+        check_at_least("reference_resolution.height", resolution.height, 1)?;
+
+        check_rect("buffer", &self.buffer.area())?;
+        check_at_least("buffer.slot_width", self.buffer.slot_width, 1)?;
+        check_rect("daemons", &self.daemons.area())?;
+        check_at_least("daemons.min_length", self.daemons.min_length, 1)?;
+        check_at_least("daemons.cell_width", self.daemons.cell_width, 1)?;
+        check_at_least("daemons.row_height", self.daemons.row_height, 1)?;
+        for (i, row) in self.daemons.rows.iter().enumerate() {
+            if row.top >= row.bottom {
+                return Err(Invalid::new(
+                    format!("daemons.rows[{}].top", i),
+                    format!(
+                        "must be less than bottom, found {} and {}",
+                        row.top, row.bottom
+                    ),
+                ));
+            }
+        }
+        check_rect("daemon_names", &self.daemon_names.area())?;
+        for (i, type_cfg) in self.daemon_names.types.iter().enumerate() {
+            if DaemonType::from_id(&type_cfg.daemon_type).is_none() {
+                return Err(Invalid::new(
+                    format!("daemon_names.types[{}].type", i),
+                    format!("unknown daemon type \"{}\"", type_cfg.daemon_type),
+                ));
+            }
+        }
+        check_rect("grid", &self.grid)?;
+        check_rect("layout.matrix_header", &self.layout.matrix_header)?;
+
+        match *self.capture.region.as_slice() {
+            [] => (),
+            [_, _, width, height] if width > 0 && height > 0 => (),
+            _ => {
+                return Err(Invalid::new(
+                    "capture.region",
+                    format!(
+                        "expected [x, y, width, height] or [], found {:?}",
+                        self.capture.region
+                    ),
+                ))
+            }
+        }
+        check_at_least("watch.change_level", self.watch.change_level, 1)?;
+
+        if self.valid_codes.is_empty() {
+            return Err(Invalid::new("valid_codes", "must not be empty".to_string()));
+        }
+
+        let checks = &self.checks;
+        check_at_least("checks.min_buffer_size", checks.min_buffer_size, 1)?;
+        check_order(
+            "checks.min_buffer_size",
+            checks.min_buffer_size,
+            "checks.max_buffer_size",
+            checks.max_buffer_size,
+        )?;
+        check_at_least("checks.min_grid_size", checks.min_grid_size, 1)?;
+        check_order(
+            "checks.min_grid_size",
+            checks.min_grid_size,
+            "checks.max_grid_size",
+            checks.max_grid_size,
+        )?;
+
+        let opencv = &self.opencv;
+        // Detection thresholds are fractions of the contrast or similarities
+        let fractions = [
+            ("opencv.detect_grid_threshold", opencv.detect_grid_threshold),
+            (
+                "opencv.detect_daemon_threshold",
+                opencv.detect_daemon_threshold,
+            ),
+            (
+                "opencv.buffer_match_threshold",
+                opencv.buffer_match_threshold,
+            ),
+            (
+                "opencv.detect_active_line_delta",
+                opencv.detect_active_line_delta,
+            ),
+        ];
+        for (key, value) in fractions {
+            check_range(key, value, 0.0..=1.0)?;
+        }
+        check_at_least("opencv.ocr_height_border", opencv.ocr_height_border, 0)?;
+        check_range(
+            "opencv.ocr_min_confidence",
+            opencv.ocr_min_confidence,
+            0..=100,
+        )?;
+        Ok(())
+    }
+}
+
+/// Drop the `$comments` notes of the settings file and of its sections
+fn remove_comments(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.remove(COMMENTS_KEY);
+            map.values_mut().for_each(remove_comments);
+        }
+        Value::Array(values) => values.iter_mut().for_each(remove_comments),
+        _ => (),
+    }
+}
+
+lazy_static! {
+    static ref SETTINGS: RwLock<Option<Arc<Settings>>> = RwLock::new(None);
+}
+
+/// Read and validate a settings file, used from now on
+pub(crate) fn load_settings(path: &Path) -> Result<(), SettingsError> {
+    let settings = Settings::read(path)?;
+    *SETTINGS.write().unwrap() = Some(Arc::new(settings));
+    Ok(())
+}
+
+/// Settings in use, loaded by `load_settings` before any scan
+pub(crate) fn settings() -> Arc<Settings> {
+    SETTINGS
+        .read()
         .unwrap()
-        .into_iter()
-        .map(|val| val.to_string())
-        .collect()
+        .clone()
+        .expect("settings used before load_settings was called")
+}
+
+/// Setting changed in a profile, with its previous value when it was set
+pub(crate) struct SettingChange {
+    pub key: String,
+    pub old: Option<Value>,
+    pub new: Value,
+}
+
+/// Write a settings profile, a copy of the `base` settings file with the values given by dotted
+/// key. The key order and the `$comments` notes of the base file are kept, but not its
+/// formatting. Returns the values which differ from the base file.
+pub(crate) fn write_profile(
+    base: &Path,
+    profile: &Path,
+    values: &[(String, Value)],
+) -> io::Result<Vec<SettingChange>> {
+    let mut settings: Value = serde_json::from_str(&fs::read_to_string(base)?)?;
+    let not_object = |key: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not inside an object in {}", key, base.display()),
+        )
+    };
+    let mut changes = Vec::new();
+    for (key, value) in values {
+        let (parents, name) = match key.rsplit_once('.') {
+            Some((parents, name)) => (Some(parents), name),
+            None => (None, key.as_str()),
+        };
+        let mut node = &mut settings;
+        for parent in parents.into_iter().flat_map(|parents| parents.split('.')) {
+            node = node
+                .as_object_mut()
+                .ok_or_else(|| not_object(key))?
+                .entry(parent)
+                .or_insert_with(|| Value::Object(Map::new()));
+        }
+        let old = node
+            .as_object_mut()
+            .ok_or_else(|| not_object(key))?
+            .insert(name.to_string(), value.clone());
+        if old.as_ref() != Some(value) {
+            changes.push(SettingChange {
+                key: key.clone(),
+                old,
+                new: value.clone(),
+            });
+        }
+    }
+    fs::write(profile, serde_json::to_string_pretty(&settings)?)?;
+    Ok(changes)
 }
-pub fn cfg_get<'de, T: Deserialize<'de>>(key: &str) -> T {
-    let settings = SETTINGS.read().unwrap();
-    (*settings).get(key).unwrap()
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde_json::json;
+    use std::sync::Once;
+
+    use super::*;
+
+    /// Load the shipped settings file, once for all the tests using `settings()`
+    pub(crate) fn load_default_settings() {
+        static LOAD: Once = Once::new();
+        LOAD.call_once(|| load_settings(Path::new(SETTINGS_FILE)).unwrap());
+    }
+
+    /// Error reading the shipped settings with a value replaced, by dotted key
+    fn error_with(key: &str, value: Value) -> SettingsError {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        write_profile(Path::new(SETTINGS_FILE), &path, &[(key.to_string(), value)]).unwrap();
+        Settings::read(&path).unwrap_err()
+    }
+
+    #[test]
+    fn read_settings() {
+        let settings = Settings::read(Path::new(SETTINGS_FILE)).unwrap();
+        assert_eq!(settings.reference_resolution.width, 1920);
+        assert_eq!(settings.grid.left, 305);
+        assert_eq!(settings.daemons.rows.len(), 4);
+        assert_eq!(settings.watch.change_level, 12);
+
+        let error = Settings::read(Path::new("config/missing.json")).unwrap_err();
+        assert_eq!(error.key, None);
+        assert!(error.to_string().starts_with("config/missing.json: "));
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let error = error_with("buffer.left", json!(1300));
+        assert_eq!(error.key.as_deref(), Some("buffer.left"));
+        assert!(error.to_string().contains("must be less than buffer.right"));
+
+        let error = error_with("opencv.detect_grid_threshold", json!(13));
+        assert_eq!(error.key.as_deref(), Some("opencv.detect_grid_threshold"));
+
+        let error = error_with("watch.change_level", json!(300));
+        assert_eq!(error.key.as_deref(), Some("watch.change_level"));
+
+        let error = error_with("daemons.row_height", json!("35"));
+        assert_eq!(error.key.as_deref(), Some("daemons.row_height"));
+
+        let error = error_with("valid_codes", json!(["1C", "XYZ"]));
+        assert_eq!(error.key.as_deref(), Some("valid_codes[1]"));
+
+        let error = error_with("opencv.ocr_engine", json!("easyocr"));
+        assert_eq!(error.key.as_deref(), Some("opencv.ocr_engine"));
+    }
+
+    #[test]
+    fn misspelled_settings() {
+        let error = error_with("opencv.ocr_threds", json!(2));
+        assert_eq!(error.key.as_deref(), Some("opencv.ocr_threds"));
+        assert!(error.to_string().contains("unknown field `ocr_threds`"));
+
+        let error = error_with("buffer.slot_widht", json!(42));
+        assert_eq!(error.key.as_deref(), Some("buffer.slot_widht"));
+
+        let error = error_with("valid_code", json!(["1C"]));
+        assert_eq!(error.key.as_deref(), Some("valid_code"));
+    }
 }
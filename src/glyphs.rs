@@ -0,0 +1,127 @@
+use opencv::core as cv;
+use opencv::imgcodecs::{imread, ImreadModes};
+use opencv::imgproc;
+use opencv::prelude::*;
+
+use crate::configuration::settings;
+use crate::ocr::{self, CellRecognizer, OcrText};
+use crate::scanner::ScanError;
+
+/// Folder with one glyph template per valid code, named after the code (e.g. `1C.png`)
+static TEMPLATES_DIR: &str = "assets/templates";
+
+/// Reference image of the glyphs of a code
+struct GlyphTemplate {
+    code: String,
+    image: Mat,
+}
+
+/// Classifier matching the glyphs of a cell against a reference template for each valid code.
+/// Templates are white glyphs on black, cropped to the glyph bounding box, all of the same size.
+pub(crate) struct GlyphClassifier {
+    templates: Vec<GlyphTemplate>,
+    size: cv::Size,
+}
+
+impl GlyphClassifier {
+    pub(crate) fn new() -> Result<GlyphClassifier, ScanError> {
+        let templates = settings()
+            .valid_codes
+            .iter()
+            .map(|code| {
+                let code = code.to_string();
+                let path = format!("{}/{}.png", TEMPLATES_DIR, code);
+                let image = imread(&path, ImreadModes::IMREAD_GRAYSCALE as i32)?;
+                if image.empty()? {
+                    return Err(ScanError::MissingAsset(path));
+                }
+                Ok(GlyphTemplate { code, image })
+            })
+            .collect::<Result<Vec<_>, ScanError>>()?;
+        let size = match templates.first() {
+            Some(template) => template.image.size()?,
+            None => return Err(ScanError::Config("valid_codes is empty".to_string())),
+        };
+        for template in &templates {
+            if template.image.size()? != size {
+                return Err(ScanError::Config(format!(
+                    "glyph template {} differs in size from the others",
+                    template.code
+                )));
+            }
+        }
+        Ok(GlyphClassifier { templates, size })
+    }
+
+    /// Binarize the cell like the OCR input, then crop the glyphs and resize them to template size
+    fn extract_glyph(&self, cell: &Mat) -> Result<Option<Mat>, ScanError> {
+        let thr_cell = ocr::binarize(cell, imgproc::THRESH_BINARY)?;
+
+        let mut points = Mat::default();
+        cv::find_non_zero(&thr_cell, &mut points)?;
+        if points.empty()? {
+            return Ok(None);
+        }
+        let bounds = imgproc::bounding_rect(&points)?;
+        let glyph = Mat::roi(&thr_cell, bounds)?;
+        let mut scaled_glyph = Mat::default();
+        imgproc::resize(
+            &glyph,
+            &mut scaled_glyph,
+            self.size,
+            0.0,
+            0.0,
+            imgproc::INTER_LINEAR,
+        )?;
+        Ok(Some(scaled_glyph))
+    }
+}
+
+impl CellRecognizer for GlyphClassifier {
+    /// Classify the cell as the code with the most similar template.
+    /// The confidence is the normalized correlation with that template.
+    fn recognize_cell(&mut self, cell: &Mat) -> Result<OcrText, ScanError> {
+        let glyph = match self.extract_glyph(cell)? {
+            Some(glyph) => glyph,
+            None => {
+                return Ok(OcrText {
+                    text: String::new(),
+                    confidence: 0,
+                })
+            }
+        };
+
+        let mut best: Option<(&GlyphTemplate, f32)> = None;
+        let mut result = Mat::default();
+        let mask = cv::no_array()?;
+        for template in &self.templates {
+            // Same size images, the result is a single similarity value
+            imgproc::match_template(
+                &glyph,
+                &template.image,
+                &mut result,
+                imgproc::TemplateMatchModes::TM_CCOEFF_NORMED as i32,
+                &mask,
+            )?;
+            let score = *result.at_2d::<f32>(0, 0)?;
+            if best.map_or(true, |(_, best_score)| score > best_score) {
+                best = Some((template, score));
+            }
+        }
+
+        Ok(best.map_or(
+            OcrText {
+                text: String::new(),
+                confidence: 0,
+            },
+            |(template, score)| OcrText {
+                text: template.code.clone(),
+                confidence: (score.clamp(0.0, 1.0) * 100.0).round() as u8,
+            },
+        ))
+    }
+
+    fn preprocess_cell(&self, cell: &Mat) -> Result<Option<Mat>, ScanError> {
+        self.extract_glyph(cell)
+    }
+}
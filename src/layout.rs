@@ -0,0 +1,374 @@
+use opencv::core as cv;
+use opencv::imgproc;
+use opencv::prelude::*;
+
+use crate::configuration::{settings, RectCfg};
+use crate::scanner::Levels;
+
+/// Maps pixel values from the reference resolution used in the settings to a captured frame.
+/// The game UI scales uniformly with the smaller frame dimension and is centered on the other one,
+/// so ultrawide or 16:10 frames are handled as a letterboxed 16:9 frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct FrameScale {
+    scale: f64,
+    offset: cv::Point2d,
+    frame: cv::Size,
+}
+
+impl FrameScale {
+    /// Create the frame scale using the reference resolution from the settings
+    pub(crate) fn new(frame: cv::Size) -> FrameScale {
+        FrameScale::with_reference(frame, reference_resolution())
+    }
+
+    pub(crate) fn with_reference(frame: cv::Size, reference: cv::Size) -> FrameScale {
+        let scale_x = frame.width as f64 / reference.width as f64;
+        let scale_y = frame.height as f64 / reference.height as f64;
+        let scale = scale_x.min(scale_y);
+        let offset = cv::Point2d::new(
+            (frame.width as f64 - reference.width as f64 * scale) / 2.0,
+            (frame.height as f64 - reference.height as f64 * scale) / 2.0,
+        );
+        FrameScale {
+            scale,
+            offset,
+            frame,
+        }
+    }
+
+    /// Create a frame scale with a known scale factor, e.g. measured from UI elements
+    pub(crate) fn with_scale(frame: cv::Size, scale: f64) -> FrameScale {
+        FrameScale {
+            scale,
+            offset: cv::Point2d::new(0.0, 0.0),
+            frame,
+        }
+    }
+
+    pub(crate) fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Scale a length, keeping it at least 1px long
+    pub(crate) fn length(&self, length: i32) -> i32 {
+        ((length as f64 * self.scale).round() as i32).max(1)
+    }
+
+    /// Scale an area, e.g. a minimum contour area
+    pub(crate) fn area(&self, area: i32) -> i32 {
+        (area as f64 * self.scale * self.scale).round() as i32
+    }
+
+    /// Scale a blur kernel size, keeping both dimensions odd as required by gaussian blur
+    pub(crate) fn odd_kernel(&self, width: i32, height: i32) -> cv::Size {
+        let odd = |length: i32| {
+            let length = self.length(length);
+            length + (1 - length % 2)
+        };
+        cv::Size::new(odd(width), odd(height))
+    }
+
+    /// Map a reference point to frame coordinates
+    pub(crate) fn point(&self, x: i32, y: i32) -> cv::Point {
+        cv::Point::new(
+            (x as f64 * self.scale + self.offset.x).round() as i32,
+            (y as f64 * self.scale + self.offset.y).round() as i32,
+        )
+    }
+
+    /// Map reference rectangle bounds to a frame rectangle, clamped to the frame area
+    pub(crate) fn rect(&self, left: i32, top: i32, right: i32, bottom: i32) -> cv::Rect {
+        let top_left = self.point(left, top);
+        let bottom_right = self.point(right, bottom);
+        let rect = cv::Rect::new(
+            top_left.x,
+            top_left.y,
+            bottom_right.x - top_left.x,
+            bottom_right.y - top_left.y,
+        );
+        clamp_rect(rect, self.frame)
+    }
+
+    /// Map a frame rectangle back to reference coordinates, the inverse of `rect`
+    pub(crate) fn reference_rect(&self, rect: cv::Rect) -> cv::Rect {
+        let reference = |value: i32, offset: f64| ((value as f64 - offset) / self.scale).round();
+        let left = reference(rect.x, self.offset.x);
+        let top = reference(rect.y, self.offset.y);
+        let right = reference(rect.x + rect.width, self.offset.x);
+        let bottom = reference(rect.y + rect.height, self.offset.y);
+        cv::Rect::new(
+            left as i32,
+            top as i32,
+            (right - left) as i32,
+            (bottom - top) as i32,
+        )
+    }
+}
+
+/// Resolution of the pixel values in the settings
+pub(crate) fn reference_resolution() -> cv::Size {
+    let resolution = settings().reference_resolution;
+    cv::Size::new(resolution.width, resolution.height)
+}
+
+/// Regions of interest of the breach protocol panel, in frame coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PanelLayout {
+    /// Scale of the panel UI with respect to the reference resolution
+    pub(crate) scale: FrameScale,
+    /// Buffer strip area
+    pub(crate) buffer: cv::Rect,
+    /// Code matrix area
+    pub(crate) grid: cv::Rect,
+    /// Daemon panel area, where the sequence rows are detected
+    pub(crate) daemons: cv::Rect,
+    /// Hinted area of each daemon sequence row, used when no row is detected
+    pub(crate) daemon_rows: Vec<cv::Rect>,
+    /// Area with the daemon names, on the right of the daemon sequences
+    pub(crate) daemon_names: cv::Rect,
+}
+
+/// Rectangle of the settings, in reference resolution coordinates
+fn settings_rect(area: &RectCfg) -> cv::Rect {
+    cv::Rect::new(
+        area.left,
+        area.top,
+        area.right - area.left,
+        area.bottom - area.top,
+    )
+}
+
+/// Daemon row hints from the settings spanning the panel width, in reference resolution
+/// coordinates
+fn settings_daemon_rows() -> Vec<cv::Rect> {
+    let settings = settings();
+    let daemons = settings.daemons.area();
+    let width = daemons.right - daemons.left;
+    settings
+        .daemons
+        .rows
+        .iter()
+        .map(|row| cv::Rect::new(daemons.left, row.top, width, row.bottom - row.top))
+        .collect()
+}
+
+impl PanelLayout {
+    /// Layout made of the fixed rectangles from the settings
+    pub(crate) fn from_settings(scale: FrameScale) -> PanelLayout {
+        let settings = settings();
+        let map =
+            |rect: cv::Rect| scale.rect(rect.x, rect.y, rect.x + rect.width, rect.y + rect.height);
+        PanelLayout {
+            scale,
+            buffer: map(settings_rect(&settings.buffer.area())),
+            grid: map(settings_rect(&settings.grid)),
+            daemons: map(settings_rect(&settings.daemons.area())),
+            daemon_rows: settings_daemon_rows().into_iter().map(map).collect(),
+            daemon_names: map(settings_rect(&settings.daemon_names.area())),
+        }
+    }
+
+    /// Locate the panel from the code matrix header, the only solid bar of its proportions on
+    /// screen. The settings rectangles are then placed relative to the detected header: the
+    /// grid is centered under it while the buffer and daemons follow its right edge, since the
+    /// matrix width changes with the grid size. The UI scale is measured from the header height.
+    pub(crate) fn locate(grey: &Mat, frame_scale: &FrameScale) -> Option<PanelLayout> {
+        let header = find_matrix_header(grey, frame_scale)?;
+        Some(PanelLayout::from_header(header, grey.size().ok()?))
+    }
+
+    /// Layout placed relative to the code matrix header found in a frame
+    pub(crate) fn from_header(header: cv::Rect, frame: cv::Size) -> PanelLayout {
+        let settings = settings();
+        let reference = settings_rect(&settings.layout.matrix_header);
+        let ui_scale = header.height as f64 / reference.height as f64;
+
+        // Map a reference rectangle keeping the offset from a reference anchor point
+        let anchored = |rect: cv::Rect, reference_x: i32, header_x: i32| {
+            let x = header_x as f64 + (rect.x - reference_x) as f64 * ui_scale;
+            let y = header.y as f64 + (rect.y - reference.y) as f64 * ui_scale;
+            let mapped = cv::Rect::new(
+                x.round() as i32,
+                y.round() as i32,
+                (rect.width as f64 * ui_scale).round() as i32,
+                (rect.height as f64 * ui_scale).round() as i32,
+            );
+            clamp_rect(mapped, frame)
+        };
+        let reference_right = reference.x + reference.width;
+        let header_right = header.x + header.width;
+        let reference_center = reference.x + reference.width / 2;
+        let header_center = header.x + header.width / 2;
+
+        let right_anchored =
+            |area: &RectCfg| anchored(settings_rect(area), reference_right, header_right);
+
+        PanelLayout {
+            scale: FrameScale::with_scale(frame, ui_scale),
+            buffer: right_anchored(&settings.buffer.area()),
+            grid: anchored(
+                settings_rect(&settings.grid),
+                reference_center,
+                header_center,
+            ),
+            daemons: right_anchored(&settings.daemons.area()),
+            daemon_rows: settings_daemon_rows()
+                .into_iter()
+                .map(|row| anchored(row, reference_right, header_right))
+                .collect(),
+            daemon_names: right_anchored(&settings.daemon_names.area()),
+        }
+    }
+}
+
+/// Find the solid header bar on top of the code matrix
+pub(crate) fn find_matrix_header(grey: &Mat, frame_scale: &FrameScale) -> Option<cv::Rect> {
+    // Keep only bright UI elements, halfway from the background to the brightest level
+    let threshold = Levels::measure(grey).ok()?.level(0.5);
+    let mut bright = Mat::default();
+    imgproc::threshold(grey, &mut bright, threshold, 255.0, imgproc::THRESH_BINARY).ok()?;
+
+    // Open the image to drop text and thin frame lines, which may join the header with the
+    // panel frame, leaving only solid areas
+    let ksize = frame_scale.length(9);
+    let anchor = cv::Point::new(-1, -1);
+    let kernel =
+        imgproc::get_structuring_element(imgproc::MORPH_RECT, cv::Size::new(ksize, ksize), anchor)
+            .ok()?;
+    let mut solid = Mat::default();
+    imgproc::morphology_ex(
+        &bright,
+        &mut solid,
+        imgproc::MORPH_OPEN,
+        &kernel,
+        anchor,
+        1,
+        cv::BORDER_CONSTANT,
+        imgproc::morphology_default_border_value().ok()?,
+    )
+    .ok()?;
+
+    let mut contours = opencv::types::VectorOfVectorOfPoint::new();
+    imgproc::find_contours(
+        &solid,
+        &mut contours,
+        imgproc::RETR_EXTERNAL,
+        imgproc::CHAIN_APPROX_SIMPLE,
+        cv::Point::new(0, 0),
+    )
+    .ok()?;
+
+    // The header is a filled bar between 10 and 20 times wider than tall
+    let min_height = frame_scale.length(20);
+    let mut best: Option<cv::Rect> = None;
+    for contour in contours.iter() {
+        let rect = match imgproc::bounding_rect(&contour) {
+            Ok(rect) => rect,
+            Err(_) => continue,
+        };
+        let aspect = rect.width as f64 / rect.height.max(1) as f64;
+        let fill = imgproc::contour_area(&contour, false).unwrap_or(0.0) / rect.area() as f64;
+        let is_header = rect.height >= min_height && (10.0..=20.0).contains(&aspect) && fill > 0.8;
+        if is_header && best.map_or(true, |best| rect.area() > best.area()) {
+            best = Some(rect);
+        }
+    }
+    best
+}
+
+/// Clamp a rectangle to the bounds of an image of the given size
+pub(crate) fn clamp_rect(rect: cv::Rect, size: cv::Size) -> cv::Rect {
+    let left = rect.x.clamp(0, size.width);
+    let top = rect.y.clamp(0, size.height);
+    let right = (rect.x + rect.width).clamp(left, size.width);
+    let bottom = (rect.y + rect.height).clamp(top, size.height);
+    cv::Rect::new(left, top, right - left, bottom - top)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::tests::load_default_settings;
+
+    #[test]
+    fn scale_16_9() {
+        let reference = cv::Size::new(1920, 1080);
+        let scale = FrameScale::with_reference(cv::Size::new(3840, 2160), reference);
+        assert_eq!(scale.scale(), 2.0);
+        assert_eq!(scale.point(830, 190), cv::Point::new(1660, 380));
+        assert_eq!(scale.length(43), 86);
+        assert_eq!(scale.area(25 * 25), 50 * 50);
+        assert_eq!(scale.odd_kernel(35, 29), cv::Size::new(71, 59));
+
+        let scale = FrameScale::with_reference(cv::Size::new(1366, 768), reference);
+        assert_eq!(scale.odd_kernel(35, 29), cv::Size::new(25, 21));
+        assert_eq!(
+            scale.rect(830, 190, 1205, 235),
+            cv::Rect::new(591, 135, 266, 32)
+        );
+        assert_eq!(
+            scale.reference_rect(cv::Rect::new(591, 135, 266, 32)),
+            cv::Rect::new(831, 190, 374, 45)
+        );
+    }
+
+    #[test]
+    fn scale_ultrawide() {
+        let reference = cv::Size::new(1920, 1080);
+        let scale = FrameScale::with_reference(cv::Size::new(2560, 1080), reference);
+        assert_eq!(scale.scale(), 1.0);
+        assert_eq!(scale.point(0, 0), cv::Point::new(320, 0));
+        assert_eq!(
+            scale.reference_rect(cv::Rect::new(320, 0, 1920, 1080)),
+            cv::Rect::new(0, 0, 1920, 1080)
+        );
+    }
+
+    fn locate_test_image(path: &str) -> (Mat, PanelLayout) {
+        let grey = opencv::imgcodecs::imread(path, opencv::imgcodecs::IMREAD_GRAYSCALE)
+            .expect(format!("File {} not found", path).as_str());
+        let frame_scale = FrameScale::new(grey.size().unwrap());
+        let layout = PanelLayout::locate(&grey, &frame_scale).expect("Panel not found");
+        (grey, layout)
+    }
+
+    #[test]
+    fn locate_panel() {
+        load_default_settings();
+        // The settings rectangles match the 6x6 screenshot
+        let (grey, layout) = locate_test_image("test/test_6x6.png");
+        let settings_layout = PanelLayout::from_settings(FrameScale::new(grey.size().unwrap()));
+        let near = |a: cv::Rect, b: cv::Rect| {
+            (a.x - b.x).abs() <= 3
+                && (a.y - b.y).abs() <= 3
+                && (a.width - b.width).abs() <= 3
+                && (a.height - b.height).abs() <= 3
+        };
+        assert!((layout.scale.scale() - 1.0).abs() < 0.05);
+        assert!(near(layout.grid, settings_layout.grid), "{:?}", layout);
+        assert!(near(layout.buffer, settings_layout.buffer), "{:?}", layout);
+
+        // The 5x5 matrix is narrower, so the buffer and daemons are shifted left
+        let (_, layout) = locate_test_image("test/test_5x5.jpg");
+        assert!(
+            layout.buffer.x < settings_layout.buffer.x - 20,
+            "{:?}",
+            layout
+        );
+        assert!(layout.daemons.x < settings_layout.daemons.x - 20);
+        assert!(layout.daemon_rows[0].x < settings_layout.daemon_rows[0].x - 20);
+    }
+
+    #[test]
+    fn clamp() {
+        let size = cv::Size::new(100, 50);
+        assert_eq!(
+            clamp_rect(cv::Rect::new(-10, 40, 50, 20), size),
+            cv::Rect::new(0, 40, 40, 10)
+        );
+        assert_eq!(
+            clamp_rect(cv::Rect::new(120, 10, 50, 20), size),
+            cv::Rect::new(100, 10, 0, 20)
+        );
+    }
+}
@@ -1,39 +1,319 @@
-use core::time;
-use std::thread;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io, process};
+
+use opencv::imgcodecs::{imread, ImreadModes};
+use opencv::prelude::*;
 
 mod configuration;
+mod glyphs;
+mod layout;
 mod ocr;
+#[cfg(windows)]
 mod overlay;
+mod render;
 mod scanner;
 mod screenshot;
 mod solver;
 mod types;
 mod utils;
+mod watch;
+#[cfg(windows)]
 mod win32;
 
+const USAGE: &str = "Usage: cyber-solve [--debug-dir <dir>] [--settings <file>] \
+     [watch [<image, directory or video>] | solve [<puzzle file>] | scan <image or directory>... | \
+     calibrate <screenshot> <puzzle file> [<profile>]]";
+
+/// Options of the commands scanning the screen or images
+#[derive(Default)]
+struct ScanOptions {
+    /// Folder where the images and summary of each scan are written
+    debug_dir: Option<PathBuf>,
+    /// Settings profile used instead of the default settings file
+    settings: Option<PathBuf>,
+}
+
+/// Take the value of an option out of the command line arguments, the last one when repeated
+fn take_option(args: &mut Vec<String>, name: &str, value: &str) -> Result<Option<String>, String> {
+    let mut taken = None;
+    while let Some(index) = args.iter().position(|arg| arg == name) {
+        if index + 1 >= args.len() {
+            return Err(format!("Missing {} after {}", value, name));
+        }
+        taken = Some(args.remove(index + 1));
+        args.remove(index);
+    }
+    Ok(taken)
+}
+
+impl ScanOptions {
+    /// Take the scan options out of the command line arguments
+    fn parse(args: &mut Vec<String>) -> Result<ScanOptions, String> {
+        Ok(ScanOptions {
+            debug_dir: take_option(args, "--debug-dir", "folder")?.map(PathBuf::from),
+            settings: take_option(args, "--settings", "file")?.map(PathBuf::from),
+        })
+    }
+
+    /// Settings file in use
+    fn settings_file(&self) -> &Path {
+        self.settings
+            .as_deref()
+            .unwrap_or_else(|| Path::new(configuration::SETTINGS_FILE))
+    }
+
+    /// Read the settings file used by the scans, or exit if it is invalid
+    fn load_settings(&self) {
+        if let Err(error) = configuration::load_settings(self.settings_file()) {
+            eprintln!("Invalid settings: {}", error);
+            process::exit(2);
+        }
+    }
+
+    /// Debug dump of a scan into a subfolder of the debug folder, or a disabled one
+    fn debug_dump(&self, name: &str) -> scanner::DebugDump {
+        let dir = match &self.debug_dir {
+            Some(dir) => dir.join(name),
+            None => return scanner::DebugDump::default(),
+        };
+        scanner::DebugDump::new(&dir).unwrap_or_else(|error| {
+            eprintln!("Debug images disabled: {}", error);
+            scanner::DebugDump::default()
+        })
+    }
+}
+
 fn main() {
-    let mut overlay = overlay::Overlay::new();
-    let five_secs = time::Duration::from_secs(5);
-    let thirty_secs = time::Duration::from_secs(30);
-
-    loop {
-        match scanner::capture_and_scan() {
-            Ok(puzzle) => {
-                let solver = solver::BreachSolver::new(&puzzle);
-                if let Some(solution) = solver.solve(solver::SolverSearchMethod::Shortest) {
-                    let grid = solver.to_grid(&solution);
-                    println!("{}", grid.to_string());
-                    overlay.show(&grid);
-                } else {
-                    println!("No solution found");
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let options = ScanOptions::parse(&mut args).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    });
+    match args.first().map(String::as_str) {
+        Some("watch") if args.len() <= 2 => watch(args.get(1).map(Path::new), &options),
+        Some("solve") => solve(args.get(1).map(String::as_str)),
+        Some("scan") if args.len() > 1 => scan_images(&args[1..], &options),
+        Some("calibrate") if (3..=4).contains(&args.len()) => calibrate(
+            Path::new(&args[1]),
+            Path::new(&args[2]),
+            args.get(3).map(Path::new),
+            &options,
+        ),
+        Some(command) => {
+            eprintln!("Unknown command \"{}\"", command);
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+        None => watch(None, &options),
+    }
+}
+
+/// Watch the screen, or replay recorded frames, and show the solution of each new puzzle
+fn watch(replay: Option<&Path>, options: &ScanOptions) {
+    options.load_settings();
+    let source = match replay {
+        Some(path) => screenshot::replay_source(path),
+        None => screenshot::screen_source(&screenshot::CaptureTarget::from_settings()),
+    };
+    let mut source = source.unwrap_or_else(|error| {
+        eprintln!("Failed to open frames: {}", error);
+        if replay.is_none() {
+            eprintln!("Replay recorded frames or use the scan command instead");
+            eprintln!("{}", USAGE);
+        }
+        process::exit(2);
+    });
+    let mut scanner = new_scanner();
+    #[cfg(windows)]
+    let mut view = overlay::Overlay::new(source.origin());
+    #[cfg(not(windows))]
+    let mut view = watch::PrintedView;
+    let summary = watch::watch(
+        source.as_mut(),
+        &mut scanner,
+        &mut view,
+        &watch::WatchIntervals::from_settings(),
+        &|name| options.debug_dump(name),
+    );
+    println!(
+        "Watched {} frames, scanned {} of them, {} failed",
+        summary.frames, summary.scans, summary.failed_scans
+    );
+}
+
+/// Create the scanner reused by every scan, loading the OCR engines and templates
+fn new_scanner() -> scanner::Scanner {
+    let scanner = scanner::Scanner::new().unwrap_or_else(|error| {
+        eprintln!("Failed to initialize scanner: {}", error);
+        process::exit(1);
+    });
+    for warning in scanner.startup_warnings() {
+        println!("Warning: {}", warning);
+    }
+    scanner
+}
+
+/// Report the codes which were recognized with low confidence, since they may be wrong
+fn print_warnings(result: &scanner::ScanResult) {
+    for warning in result.warnings() {
+        println!("Warning: {}", warning);
+    }
+}
+
+/// Solve a puzzle in text format read from a file, or from stdin when no file is given
+fn solve(path: Option<&str>) {
+    let text = match path {
+        Some(path) => fs::read_to_string(path),
+        None => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map(|_| text)
+        }
+    };
+    let text = text.unwrap_or_else(|error| {
+        eprintln!("Failed to read puzzle: {}", error);
+        process::exit(1);
+    });
+    let puzzle: types::Puzzle = text.parse().unwrap_or_else(|error| {
+        eprintln!("Invalid puzzle: {}", error);
+        process::exit(1);
+    });
+
+    let solver = solver::BreachSolver::new(&puzzle);
+    match solver.solve(solver::SolverSearchMethod::Priority) {
+        Some(solution) => {
+            let colors = render::colors_supported();
+            println!("{}", render::render_solution(&puzzle, &solution, colors));
+        }
+        None => println!("No solution found"),
+    }
+}
+
+/// Expand directories in `paths` to the image files they contain, sorted by name
+fn collect_images(paths: &[String]) -> io::Result<Vec<PathBuf>> {
+    let mut images = Vec::new();
+    for path in paths.iter().map(Path::new) {
+        if path.is_dir() {
+            images.append(&mut screenshot::list_images(path)?);
+        } else {
+            images.push(path.to_path_buf());
+        }
+    }
+    Ok(images)
+}
+
+/// Scan puzzles from image files and print each puzzle with its solution
+fn scan_images(paths: &[String], options: &ScanOptions) {
+    options.load_settings();
+    let images = collect_images(paths).unwrap_or_else(|error| {
+        eprintln!("Failed to list images: {}", error);
+        process::exit(1);
+    });
+    let mut scanner = new_scanner();
+    let colors = render::colors_supported();
+    let mut failed = 0;
+    for image in images.iter() {
+        println!("== {}", image.display());
+        let screen = imread(
+            &image.to_string_lossy(),
+            ImreadModes::IMREAD_UNCHANGED as i32,
+        )
+        .ok()
+        .filter(|screen| !screen.empty().unwrap_or(true));
+        let screen = match screen {
+            Some(screen) => screen,
+            None => {
+                eprintln!("Failed to read image {}", image.display());
+                failed += 1;
+                continue;
+            }
+        };
+        let name = image
+            .file_stem()
+            .map_or("image".into(), |stem| stem.to_string_lossy());
+        let mut debug = options.debug_dump(&name);
+        match scanner.scan_debug(&screen, &mut debug) {
+            Ok(result) => {
+                let puzzle = &result.puzzle;
+                println!("{}", puzzle);
+                if result.state.is_started() {
+                    println!("Puzzle in progress: {}", result.state);
+                }
+                print_warnings(&result);
+                let solver = solver::BreachSolver::with_state(puzzle, &result.state);
+                match solver.solve(solver::SolverSearchMethod::Priority) {
+                    Some(solution) => {
+                        println!("{}", render::render_solution(puzzle, &solution, colors))
+                    }
+                    None => println!("No solution found"),
                 }
-                thread::sleep(thirty_secs);
             }
-            Err(msg) => {
-                println!("Scan failed: {}", &msg);
-                overlay.hide();
-                thread::sleep(five_secs);
+            Err(error) => {
+                eprintln!("Scan failed: {}", error);
+                failed += 1;
             }
         }
     }
+    if images.len() > 1 {
+        println!("Scanned {} images, {} failed", images.len(), failed);
+    }
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+/// Measure the panel layout and the thresholds on a screenshot of a known puzzle, and write them
+/// to a new settings profile, by default named after the screenshot resolution
+fn calibrate(screenshot: &Path, puzzle: &Path, profile: Option<&Path>, options: &ScanOptions) {
+    options.load_settings();
+    let expected: types::Puzzle = fs::read_to_string(puzzle)
+        .map_err(|error| error.to_string())
+        .and_then(|text| {
+            text.parse()
+                .map_err(|error: types::ParsePuzzleError| error.to_string())
+        })
+        .unwrap_or_else(|error| {
+            eprintln!("Invalid puzzle {}: {}", puzzle.display(), error);
+            process::exit(1);
+        });
+    let screen = imread(
+        &screenshot.to_string_lossy(),
+        ImreadModes::IMREAD_UNCHANGED as i32,
+    )
+    .ok()
+    .filter(|screen| !screen.empty().unwrap_or(true))
+    .unwrap_or_else(|| {
+        eprintln!("Failed to read image {}", screenshot.display());
+        process::exit(1);
+    });
+
+    let mut scanner = new_scanner();
+    let mut debug = options.debug_dump("calibrate");
+    let calibration = scanner
+        .calibrate(&screen, &expected, &mut debug)
+        .unwrap_or_else(|error| {
+            eprintln!("Failed to calibrate on {}: {}", screenshot.display(), error);
+            process::exit(1);
+        });
+
+    let default_profile = format!("config/settings-{}x{}.json", screen.cols(), screen.rows());
+    let profile = profile.unwrap_or_else(|| Path::new(&default_profile));
+    let changes =
+        configuration::write_profile(options.settings_file(), profile, &calibration.settings())
+            .unwrap_or_else(|error| {
+                eprintln!("Failed to write profile {}: {}", profile.display(), error);
+                process::exit(1);
+            });
+    println!("Settings profile written to {}", profile.display());
+    if changes.is_empty() {
+        println!("No setting changed");
+    }
+    for change in changes {
+        match change.old {
+            Some(old) => println!("  {}: {} -> {}", change.key, old, change.new),
+            None => println!("  {}: {}", change.key, change.new),
+        }
+    }
+    println!("Use it with: cyber-solve --settings {}", profile.display());
 }
@@ -0,0 +1,126 @@
+mod pool;
+#[cfg(feature = "tesseract")]
+mod tesseract;
+
+use opencv::core as cv;
+use opencv::imgproc;
+use opencv::prelude::*;
+
+use crate::configuration::{settings, OcrEngine};
+use crate::glyphs::GlyphClassifier;
+use crate::scanner::ScanError;
+
+pub(crate) use pool::{read_cell, CellReading, OcrPool};
+#[cfg(feature = "tesseract")]
+pub(crate) use tesseract::{TesseractRecognizer, TesseractTextRecognizer};
+
+/// Text recognized by the OCR engine
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct OcrText {
+    pub text: String,
+    /// Mean recognition confidence in percent
+    pub confidence: u8,
+}
+
+/// Engine reading the code shown in a cell image.
+/// Cells are greyscale crops scaled to the reference resolution.
+pub(crate) trait CellRecognizer {
+    fn recognize_cell(&mut self, cell: &Mat) -> Result<OcrText, ScanError>;
+
+    /// Cell image as given to the engine after preprocessing, for debugging
+    fn preprocess_cell(&self, _cell: &Mat) -> Result<Option<Mat>, ScanError> {
+        Ok(None)
+    }
+}
+
+/// Engine reading a line of free text, such as a daemon name.
+/// Images are greyscale crops scaled to the reference resolution.
+pub(crate) trait TextRecognizer {
+    fn recognize_text(&mut self, image: &Mat) -> Result<OcrText, ScanError>;
+}
+
+/// Binary image of the glyphs of a cell or text image. The image is stretched to the full grey
+/// range, then split into glyphs and background with Otsu's method, so that neither brightness
+/// nor contrast matter. `threshold_type` is `THRESH_BINARY` or `THRESH_BINARY_INV`.
+pub(crate) fn binarize(image: &Mat, threshold_type: i32) -> Result<Mat, ScanError> {
+    let mut normalized = Mat::default();
+    let no_mask = cv::no_array()?;
+    cv::normalize(
+        image,
+        &mut normalized,
+        255.0,
+        0.0,
+        cv::NORM_MINMAX,
+        -1,
+        &no_mask,
+    )?;
+    let mut binary = Mat::default();
+    imgproc::threshold(
+        &normalized,
+        &mut binary,
+        0.0,
+        255.0,
+        threshold_type | imgproc::THRESH_OTSU,
+    )?;
+    Ok(binary)
+}
+
+/// Create the engine selected by the `opencv.ocr_engine` setting
+pub(crate) fn recognizer() -> Result<Box<dyn CellRecognizer>, ScanError> {
+    match settings().opencv.ocr_engine {
+        #[cfg(feature = "tesseract")]
+        OcrEngine::Tesseract => Ok(Box::new(TesseractRecognizer::new()?)),
+        #[cfg(not(feature = "tesseract"))]
+        OcrEngine::Tesseract => Err(ScanError::Config(
+            "ocr_engine \"tesseract\" needs the tesseract feature, use \"templates\"".to_string(),
+        )),
+        OcrEngine::Templates => Ok(Box::new(GlyphClassifier::new()?)),
+    }
+}
+
+/// Create the engine reading the daemon names, which needs tesseract.
+/// Without the tesseract feature there is none and daemon names are not read.
+pub(crate) fn text_recognizer() -> Result<Option<Box<dyn TextRecognizer>>, ScanError> {
+    #[cfg(feature = "tesseract")]
+    let recognizer: Option<Box<dyn TextRecognizer>> =
+        Some(Box::new(TesseractTextRecognizer::new()?));
+    #[cfg(not(feature = "tesseract"))]
+    let recognizer = None;
+    Ok(recognizer)
+}
+
+/// Recognizer returning scripted texts in order, for testing the scanner without an OCR engine
+#[cfg(test)]
+pub(crate) struct ScriptedRecognizer {
+    texts: std::collections::VecDeque<OcrText>,
+}
+
+#[cfg(test)]
+impl ScriptedRecognizer {
+    pub(crate) fn new(texts: &[(&str, u8)]) -> ScriptedRecognizer {
+        let texts = texts
+            .iter()
+            .map(|&(text, confidence)| OcrText {
+                text: text.to_string(),
+                confidence,
+            })
+            .collect();
+        ScriptedRecognizer { texts }
+    }
+}
+
+#[cfg(test)]
+impl CellRecognizer for ScriptedRecognizer {
+    fn recognize_cell(&mut self, _cell: &Mat) -> Result<OcrText, ScanError> {
+        self.texts
+            .pop_front()
+            .ok_or_else(|| ScanError::Ocr("no more scripted texts".to_string()))
+    }
+}
+
+#[cfg(test)]
+impl TextRecognizer for ScriptedRecognizer {
+    fn recognize_text(&mut self, image: &Mat) -> Result<OcrText, ScanError> {
+        self.recognize_cell(image)
+    }
+}
@@ -0,0 +1,245 @@
+use std::sync::mpsc;
+use std::thread;
+
+use opencv::prelude::*;
+
+use super::{CellRecognizer, OcrText};
+use crate::scanner::ScanError;
+
+/// Text of a cell, with the cell image as preprocessed by the engine when asked for
+pub(crate) type CellReading = (OcrText, Option<Mat>);
+
+/// Creates the OCR engine of a worker thread
+pub(crate) type EngineFactory = fn() -> Result<Box<dyn CellRecognizer>, ScanError>;
+
+/// Cell image to read, with the index of its result
+struct CellJob {
+    index: usize,
+    cell: Mat,
+    preprocess: bool,
+    results: mpsc::Sender<(usize, Result<CellReading, ScanError>)>,
+}
+
+struct Worker {
+    jobs: Option<mpsc::Sender<CellJob>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+/// Worker threads with an OCR engine each, reading the cells of a batch in parallel.
+/// Engines are created on their own thread and never leave it, so they need not be `Send`.
+pub(crate) struct OcrPool {
+    workers: Vec<Worker>,
+}
+
+impl OcrPool {
+    /// Start the worker threads, failing if an engine cannot be created
+    pub(crate) fn new(threads: usize, engine: EngineFactory) -> Result<OcrPool, ScanError> {
+        let mut workers = Vec::new();
+        let (ready_sender, ready) = mpsc::channel();
+        for _ in 0..threads.max(1) {
+            let (jobs, job_receiver) = mpsc::channel::<CellJob>();
+            let ready_sender = ready_sender.clone();
+            let thread = thread::spawn(move || {
+                let mut ocr = match engine() {
+                    Ok(ocr) => {
+                        ready_sender.send(Ok(())).ok();
+                        ocr
+                    }
+                    Err(error) => {
+                        ready_sender.send(Err(error)).ok();
+                        return;
+                    }
+                };
+                drop(ready_sender);
+                for job in job_receiver {
+                    let reading = read_cell(ocr.as_mut(), &job.cell, job.preprocess);
+                    job.results.send((job.index, reading)).ok();
+                }
+            });
+            workers.push(Worker {
+                jobs: Some(jobs),
+                thread: Some(thread),
+            });
+        }
+        drop(ready_sender);
+        // The pool is dropped on error, which stops the workers
+        let pool = OcrPool { workers };
+        for _ in 0..pool.workers.len() {
+            ready
+                .recv()
+                .map_err(|_| ScanError::Ocr("OCR worker thread stopped".to_string()))??;
+        }
+        Ok(pool)
+    }
+
+    /// Read the cells on the worker threads, with the results in the order of the cells
+    pub(crate) fn recognize_cells(
+        &self,
+        cells: Vec<Mat>,
+        preprocess: bool,
+    ) -> Result<Vec<CellReading>, ScanError> {
+        let count = cells.len();
+        let (results, result_receiver) = mpsc::channel();
+        for (index, cell) in cells.into_iter().enumerate() {
+            // Cells in turn to each worker, which spreads slow rows of codes
+            let worker = &self.workers[index % self.workers.len()];
+            let job = CellJob {
+                index,
+                cell,
+                preprocess,
+                results: results.clone(),
+            };
+            let sent = worker.jobs.as_ref().map(|jobs| jobs.send(job).is_ok());
+            if sent != Some(true) {
+                return Err(ScanError::Ocr("OCR worker thread stopped".to_string()));
+            }
+        }
+        drop(results);
+
+        let mut readings: Vec<Option<CellReading>> = (0..count).map(|_| None).collect();
+        for (index, reading) in result_receiver.iter().take(count) {
+            readings[index] = Some(reading?);
+        }
+        readings
+            .into_iter()
+            .map(|reading| {
+                reading.ok_or_else(|| ScanError::Ocr("OCR worker thread stopped".to_string()))
+            })
+            .collect()
+    }
+}
+
+impl Drop for OcrPool {
+    fn drop(&mut self) {
+        // Closing the job channels ends the worker loops
+        for worker in self.workers.iter_mut() {
+            worker.jobs.take();
+        }
+        for worker in self.workers.iter_mut() {
+            if let Some(thread) = worker.thread.take() {
+                thread.join().ok();
+            }
+        }
+    }
+}
+
+/// Read a cell with an engine, along with the preprocessed image when asked for
+pub(crate) fn read_cell(
+    ocr: &mut dyn CellRecognizer,
+    cell: &Mat,
+    preprocess: bool,
+) -> Result<CellReading, ScanError> {
+    let preprocessed = if preprocess {
+        ocr.preprocess_cell(cell)?
+    } else {
+        None
+    };
+    Ok((ocr.recognize_cell(cell)?, preprocessed))
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::core as cv;
+
+    use super::*;
+
+    /// Engine reading the mean level of the cell, slower for darker cells
+    struct LevelRecognizer;
+
+    impl CellRecognizer for LevelRecognizer {
+        fn recognize_cell(&mut self, cell: &Mat) -> Result<OcrText, ScanError> {
+            let level = cv::mean(cell, &cv::no_array()?)?[0] as u8;
+            thread::sleep(std::time::Duration::from_millis((255 - level as u64) / 10));
+            Ok(OcrText {
+                text: level.to_string(),
+                confidence: 100,
+            })
+        }
+    }
+
+    #[test]
+    fn recognize_in_order() {
+        let pool = OcrPool::new(4, || {
+            Ok(Box::new(LevelRecognizer) as Box<dyn CellRecognizer>)
+        });
+        let pool = pool.unwrap();
+        let levels: Vec<u8> = (0..20).map(|n| 250 - n * 10).collect();
+        let cells = levels
+            .iter()
+            .map(|&level| {
+                Mat::new_rows_cols_with_default(4, 4, cv::CV_8UC1, cv::Scalar::all(level as f64))
+                    .unwrap()
+            })
+            .collect();
+        let texts: Vec<String> = pool
+            .recognize_cells(cells, false)
+            .unwrap()
+            .into_iter()
+            .map(|(text, _)| text.text)
+            .collect();
+        let expected: Vec<String> = levels.iter().map(u8::to_string).collect();
+        assert_eq!(texts, expected);
+    }
+
+    /// Engine failing on the dark cells
+    struct BrightRecognizer;
+
+    impl CellRecognizer for BrightRecognizer {
+        fn recognize_cell(&mut self, cell: &Mat) -> Result<OcrText, ScanError> {
+            let level = cv::mean(cell, &cv::no_array()?)?[0] as u8;
+            if level < 100 {
+                return Err(ScanError::Ocr(format!("dark cell {}", level)));
+            }
+            Ok(OcrText {
+                text: level.to_string(),
+                confidence: 100,
+            })
+        }
+    }
+
+    fn level_cells(levels: &[u8]) -> Vec<Mat> {
+        levels
+            .iter()
+            .map(|&level| {
+                Mat::new_rows_cols_with_default(4, 4, cv::CV_8UC1, cv::Scalar::all(level as f64))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_readings_as_one_engine() {
+        let pool = OcrPool::new(3, || {
+            Ok(Box::new(BrightRecognizer) as Box<dyn CellRecognizer>)
+        })
+        .unwrap();
+        let mut engine = BrightRecognizer;
+        // The pool is reused for several batches, including a failed one
+        for levels in [
+            vec![200, 150, 250, 120],
+            vec![110],
+            vec![],
+            vec![200, 50, 180],
+        ] {
+            let cells = level_cells(&levels);
+            let expected: Result<Vec<String>, ScanError> = cells
+                .iter()
+                .map(|cell| read_cell(&mut engine, cell, false).map(|(text, _)| text.text))
+                .collect();
+            let readings: Result<Vec<String>, ScanError> = pool
+                .recognize_cells(cells, false)
+                .map(|readings| readings.into_iter().map(|(text, _)| text.text).collect());
+            match (readings, expected) {
+                (Ok(readings), Ok(expected)) => assert_eq!(readings, expected),
+                (Err(ScanError::Ocr(error)), Err(_)) => assert_eq!(error, "dark cell 50"),
+                (readings, expected) => panic!("{:?} instead of {:?}", readings, expected),
+            }
+        }
+    }
+
+    #[test]
+    fn engine_errors() {
+        let error = OcrPool::new(2, || Err(ScanError::Ocr("no engine".to_string())));
+        assert!(matches!(error, Err(ScanError::Ocr(_))));
+    }
+}
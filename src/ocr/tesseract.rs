@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+
+use leptess::LepTess;
+use opencv::core as cv;
+use opencv::imgproc;
+use opencv::prelude::*;
+
+use super::{binarize, CellRecognizer, OcrText, TextRecognizer};
+use crate::configuration::settings;
+use crate::scanner::ScanError;
+use crate::types::Code;
+
+/// Cell recognizer reading the text with tesseract, restricted to the characters of the valid codes
+pub(crate) struct TesseractRecognizer {
+    leptess: LepTess,
+}
+
+fn init_leptess() -> Result<LepTess, ScanError> {
+    LepTess::new(Some("./assets/tesseract"), "eng")
+        .map_err(|e| ScanError::Ocr(format!("failed to initialize tesseract: {}", e)))
+}
+
+/// Read the text of a PNG encoded image
+fn recognize_png(leptess: &mut LepTess, buf: &[u8]) -> Result<OcrText, ScanError> {
+    // Send image to leptess
+    leptess
+        .set_image_from_mem(buf)
+        .map_err(|e| ScanError::Ocr(e.to_string()))?;
+
+    // Set dpi after image update
+    let dpi = 70;
+    leptess.set_source_resolution(dpi);
+
+    let text = leptess
+        .get_utf8_text()
+        .map_err(|e| ScanError::Ocr(e.to_string()))?
+        .trim_end()
+        .to_uppercase();
+    let confidence = leptess.mean_text_conf().clamp(0, 100) as u8;
+    Ok(OcrText { text, confidence })
+}
+
+impl TesseractRecognizer {
+    pub(crate) fn new() -> Result<TesseractRecognizer, ScanError> {
+        let mut leptess = init_leptess()?;
+
+        // Set character whitelist
+        let valid_chars: String = settings().valid_codes.iter().map(Code::to_string).collect();
+        let valid_char_set: HashSet<String> = valid_chars.chars().map(String::from).collect();
+        let whitelist: String = valid_char_set
+            .iter()
+            .fold("".to_string(), |acc, char| (acc + &char));
+        leptess
+            .set_variable(leptess::Variable::TesseditCharWhitelist, &whitelist)
+            .map_err(|e| ScanError::Ocr(format!("failed to set tesseract whitelist: {}", e)))?;
+
+        Ok(TesseractRecognizer { leptess })
+    }
+
+    /// Binary image of the cell with dark text on white, as read by tesseract
+    fn preprocess(cell: &Mat) -> Result<Mat, ScanError> {
+        // Tunable params
+        let height_border = settings().opencv.ocr_height_border;
+
+        // Make binary image with inverted colors
+        let thr_cell = binarize(cell, imgproc::THRESH_BINARY_INV)?;
+
+        // Extend top-bottom border
+        let white = cv::Scalar::new(255.0, 255.0, 255.0, 255.0);
+        let mut border_cell = Mat::default();
+        cv::copy_make_border(
+            &thr_cell,
+            &mut border_cell,
+            height_border,
+            height_border,
+            0,
+            0,
+            cv::BORDER_CONSTANT,
+            white,
+        )?;
+
+        Ok(border_cell)
+    }
+}
+
+impl CellRecognizer for TesseractRecognizer {
+    fn recognize_cell(&mut self, cell: &Mat) -> Result<OcrText, ScanError> {
+        let border_cell = TesseractRecognizer::preprocess(cell)?;
+        let mut buffer = cv::Vector::new();
+        opencv::imgcodecs::imencode(".png", &border_cell, &mut buffer, &cv::Vector::new())?;
+
+        let text = recognize_png(&mut self.leptess, &buffer.to_vec())?;
+        Ok(text)
+    }
+
+    fn preprocess_cell(&self, cell: &Mat) -> Result<Option<Mat>, ScanError> {
+        Ok(Some(TesseractRecognizer::preprocess(cell)?))
+    }
+}
+
+/// Text recognizer reading a single line of any characters with tesseract
+pub(crate) struct TesseractTextRecognizer {
+    leptess: LepTess,
+}
+
+impl TesseractTextRecognizer {
+    pub(crate) fn new() -> Result<TesseractTextRecognizer, ScanError> {
+        let mut leptess = init_leptess()?;
+        // Single line of text
+        leptess
+            .set_variable(leptess::Variable::TesseditPagesegMode, "7")
+            .map_err(|e| ScanError::Ocr(format!("failed to set tesseract page mode: {}", e)))?;
+        Ok(TesseractTextRecognizer { leptess })
+    }
+}
+
+impl TextRecognizer for TesseractTextRecognizer {
+    fn recognize_text(&mut self, image: &Mat) -> Result<OcrText, ScanError> {
+        let border_image = TesseractRecognizer::preprocess(image)?;
+        let mut buffer = cv::Vector::new();
+        opencv::imgcodecs::imencode(".png", &border_image, &mut buffer, &cv::Vector::new())?;
+        recognize_png(&mut self.leptess, &buffer.to_vec())
+    }
+}
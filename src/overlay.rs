@@ -1,5 +1,6 @@
-use crate::types::PuzzleGrid;
+use crate::types::{CellCoord, PuzzleGrid};
 use crate::utils::{lerp_i, Color};
+use crate::watch::SolutionView;
 use crate::win32::overlay_window::OverlayController;
 use opencv::core as cv;
 use opencv::imgcodecs::imencode;
@@ -9,18 +10,46 @@ use opencv::prelude::*;
 
 pub(crate) struct Overlay {
     controller: Option<OverlayController>,
+    /// Position of the captured frames on the desktop, which the overlay area is relative to
+    origin: cv::Point,
+    /// Area of the captured frames where the solution was last shown
+    shown: Option<cv::Rect>,
 }
 
 impl Overlay {
-    pub(crate) fn new() -> Self {
-        Self { controller: None }
+    pub(crate) fn new(origin: cv::Point) -> Self {
+        Self {
+            controller: None,
+            origin,
+            shown: None,
+        }
+    }
+
+    fn load_overlay_image(&mut self, x: i32, y: i32, width: i32, height: i32, bytes: &[u8]) -> () {
+        if self.controller.is_none() {
+            self.controller = Some(OverlayController::new(x, y, width, height));
+            println!("Create new overlay");
+        }
+        let controller = self.controller.as_ref().unwrap();
+        controller.load(x, y, width, height, &bytes);
+        println!("Loaded overlay image");
+    }
+}
+
+impl SolutionView for Overlay {
+    /// Area of the captured frames covered by the overlay, once shown
+    fn area(&self) -> Option<cv::Rect> {
+        self.shown
     }
 
-    pub(crate) fn show(&mut self, grid: &PuzzleGrid) -> () {
-        let x = 852;
-        let y = 715;
-        let overlay_width = 216;
-        let overlay_height = 216;
+    /// Show the solution steps grid over the code matrix, marking the cells which may have been
+    /// misread
+    fn show(&mut self, grid: &PuzzleGrid<String>, area: cv::Rect, uncertain: &[CellCoord]) {
+        self.shown = Some(area);
+        // The overlay window is placed in desktop coordinates
+        let (x, y) = (self.origin.x + area.x, self.origin.y + area.y);
+        let overlay_width = area.width;
+        let overlay_height = area.height;
         let image_upscaling = 2.0;
         let image_height = (image_upscaling * overlay_height as f64).floor() as i32;
         let image_width = (image_upscaling * overlay_width as f64).floor() as i32;
@@ -30,7 +59,7 @@ impl Overlay {
         let mut img =
             Mat::new_rows_cols_with_default(image_height, image_width, cv::CV_8UC4, bg_color)
                 .unwrap();
-        draw_grid(&mut img, &grid);
+        draw_grid(&mut img, &grid, uncertain);
 
         // Encode image bytes to PNG format
         let mut bytes = cv::Vector::new();
@@ -41,29 +70,20 @@ impl Overlay {
         self.load_overlay_image(x, y, overlay_width, overlay_height, &bytes);
     }
 
-    pub(crate) fn hide(&mut self) -> () {
+    fn hide(&mut self) {
         if let Some(controller) = &self.controller {
             controller.hide();
         }
     }
-
-    fn load_overlay_image(&mut self, x: i32, y: i32, width: i32, height: i32, bytes: &[u8]) -> () {
-        if self.controller.is_none() {
-            self.controller = Some(OverlayController::new(x, y, width, height));
-            println!("Create new overlay");
-        }
-        let controller = self.controller.as_ref().unwrap();
-        controller.load(&bytes);
-        println!("Loaded overlay image");
-    }
 }
 
-fn draw_grid(img: &mut Mat, grid: &PuzzleGrid) -> () {
+fn draw_grid(img: &mut Mat, grid: &PuzzleGrid<String>, uncertain: &[CellCoord]) -> () {
     let rows = grid.rows;
     let cols = grid.cols;
     let height = img.rows();
     let width = img.cols();
     let cyber_yellow = Color::rgba(0xcf, 0xed, 0x56, 0xff).to_bgra(); // #cfed56
+    let warning_red = Color::rgba(0xff, 0x5f, 0x56, 0xff).to_bgra(); // #ff5f56
     let thickness = 4;
     let line_type = imgproc::LineTypes::LINE_8 as i32;
 
@@ -129,6 +149,34 @@ fn draw_grid(img: &mut Mat, grid: &PuzzleGrid) -> () {
                 false,
             )
             .unwrap();
+
+            // Frame cells with a doubtful code and mark them with a question mark
+            if uncertain.contains(&(row, col)) {
+                let inset = 2 * thickness;
+                let cell_rect = cv::Rect::new(
+                    grid_top_left.x + x_offset + inset,
+                    grid_top_left.y + y_offset + inset,
+                    cell_width - 2 * inset,
+                    cell_height - 2 * inset,
+                );
+                imgproc::rectangle(img, cell_rect, warning_red, thickness, line_type, 0).unwrap();
+                let mark_origin = cv::Point::new(
+                    cell_rect.x + cell_rect.width - 2 * inset,
+                    cell_rect.y + 3 * inset,
+                );
+                imgproc::put_text(
+                    img,
+                    "?",
+                    mark_origin,
+                    font_face,
+                    font_scale / 2.0,
+                    warning_red,
+                    thickness / 2,
+                    text_line_type,
+                    false,
+                )
+                .unwrap();
+            }
         }
     }
     // debug_show("overlay grid cells", img);
@@ -141,16 +189,17 @@ mod tests {
 
     use crate::overlay::*;
     use crate::types::PuzzleGrid;
+    use crate::watch::SolutionView;
 
     #[test]
     fn test_overlay() {
         // Make 1,2,...,25 test grid
         let size = 5;
         let cells: Vec<String> = (0..size * size).map(|i| i.to_string().to_owned()).collect();
-        let grid = PuzzleGrid::from_cells(size, size, cells);
+        let grid = PuzzleGrid::try_from_cells(size, size, cells).unwrap();
 
-        let mut overlay = Overlay::new();
-        overlay.show(&grid);
+        let mut overlay = Overlay::new(cv::Point::new(0, 0));
+        overlay.show(&grid, cv::Rect::new(852, 715, 216, 216), &[(0, 0), (2, 3)]);
         sleep(Duration::from_secs(3));
         overlay.hide();
     }
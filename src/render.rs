@@ -0,0 +1,284 @@
+use std::io::IsTerminal;
+use std::ops::Range;
+
+use crate::types::*;
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_BOLD: &str = "\x1b[1m";
+const ANSI_DIM: &str = "\x1b[2m";
+/// Foreground colors assigned to daemons, in daemon order
+const DAEMON_COLORS: [&str; 6] = [
+    "\x1b[33m", // yellow
+    "\x1b[36m", // cyan
+    "\x1b[35m", // magenta
+    "\x1b[32m", // green
+    "\x1b[34m", // blue
+    "\x1b[31m", // red
+];
+/// Marker drawn on the last cell of the path
+const PATH_END: char = '■';
+
+/// Check if ANSI colors can be used on stdout
+pub(crate) fn colors_supported() -> bool {
+    std::env::var_os("NO_COLOR").is_none()
+        && std::env::var("TERM").map_or(true, |term| term != "dumb")
+        && std::io::stdout().is_terminal()
+}
+
+/// Find the buffer range where each daemon is uploaded, or None if the daemon is not uploaded
+fn daemon_uploads(puzzle: &Puzzle, solution: &PuzzleSolution) -> Vec<Option<Range<usize>>> {
+    puzzle
+        .daemons
+        .iter()
+        .map(|daemon| {
+            if daemon.is_empty() {
+                return None;
+            }
+            solution
+                .buffer
+                .windows(daemon.len())
+                .position(|codes| codes == daemon.as_slice())
+                .map(|start| start..start + daemon.len())
+        })
+        .collect()
+}
+
+/// Arrow pointing from a cell to the next one in the path
+fn arrow(from: CellCoord, to: CellCoord) -> char {
+    match (from, to) {
+        ((from_row, from_col), (to_row, to_col)) if from_row == to_row => {
+            if to_col > from_col {
+                '→'
+            } else {
+                '←'
+            }
+        }
+        ((from_row, _), (to_row, _)) => {
+            if to_row > from_row {
+                '↓'
+            } else {
+                '↑'
+            }
+        }
+    }
+}
+
+fn paint(text: &str, style: Option<&str>) -> String {
+    match style {
+        Some(style) => format!("{}{}{}", style, text, ANSI_RESET),
+        None => text.to_string(),
+    }
+}
+
+/// Render the puzzle grid with the solution path on top of the cell codes.
+/// Each path cell shows its step number and an arrow to the next cell, cells which upload a
+/// daemon are highlighted with the daemon color, followed by a legend of the daemons.
+pub(crate) fn render_solution(puzzle: &Puzzle, solution: &PuzzleSolution, colors: bool) -> String {
+    let grid = &puzzle.grid;
+    let coords = solution.to_coords();
+    let uploads = daemon_uploads(puzzle, solution);
+    // Codes already in the buffer before the first step, when continuing a partial puzzle
+    let offset = solution.buffer.len().saturating_sub(coords.len());
+    let cell_span: usize = 8;
+
+    // Content and style of each cell
+    let cell_text = |row: u32, col: u32| -> (String, Option<&str>) {
+        let code = grid.get_cell(row, col);
+        match coords.iter().position(|&coord| coord == (row, col)) {
+            Some(step) => {
+                let marker = coords
+                    .get(step + 1)
+                    .map_or(PATH_END, |&next| arrow((row, col), next));
+                let daemon = uploads.iter().position(|upload| {
+                    upload
+                        .as_ref()
+                        .map_or(false, |range| range.contains(&(step + offset)))
+                });
+                let style = match daemon {
+                    Some(n) => DAEMON_COLORS[n % DAEMON_COLORS.len()],
+                    None => ANSI_BOLD,
+                };
+                (format!("{} {:>2}{}", code, step + 1, marker), Some(style))
+            }
+            None => (format!("{}    ", code), Some(ANSI_DIM)),
+        }
+    };
+
+    let border = |left: &str, middle: &str, right: &str| {
+        let line = vec!["─".repeat(cell_span); grid.cols as usize].join(middle);
+        format!("{}{}{}\n", left, line, right)
+    };
+
+    let mut text = border("┌", "┬", "┐");
+    for row in 0..grid.rows {
+        if row > 0 {
+            text += &border("├", "┼", "┤");
+        }
+        let cells: Vec<String> = (0..grid.cols)
+            .map(|col| {
+                let (content, style) = cell_text(row, col);
+                let padded = format!("{:^width$}", content, width = cell_span);
+                paint(&padded, style.filter(|_| colors))
+            })
+            .collect();
+        text += &format!("│{}│\n", cells.join("│"));
+    }
+    text += &border("└", "┴", "┘");
+
+    // Legend
+    let codes = |codes: &[Code]| {
+        codes
+            .iter()
+            .map(|code| code.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    text += &format!("Buffer: {}\n", codes(&solution.buffer));
+    text += "Daemons:\n";
+    for (n, (daemon, upload)) in puzzle.daemons.iter().zip(uploads.iter()).enumerate() {
+        let status = match upload {
+            Some(range) if range.end <= offset => "already uploaded".to_string(),
+            Some(range) => format!(
+                "uploaded at steps {}-{}",
+                range.start.max(offset) - offset + 1,
+                range.end - offset
+            ),
+            None => "not uploaded".to_string(),
+        };
+        let marker = paint(
+            &format!("#{}", n + 1),
+            Some(DAEMON_COLORS[n % DAEMON_COLORS.len()]).filter(|_| colors),
+        );
+        let daemon_type = match puzzle.daemon_type(n) {
+            DaemonType::Unknown => String::new(),
+            daemon_type => format!(" ({}: {})", daemon_type, daemon_type.reward()),
+        };
+        text += &format!(
+            "  {} {:<width$} {}{}\n",
+            marker,
+            codes(daemon),
+            status,
+            daemon_type,
+            width = 3 * puzzle.buffer_size as usize
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{BreachSolver, SolverSearchMethod};
+
+    fn solve(text: &str) -> (Puzzle, PuzzleSolution) {
+        let puzzle: Puzzle = text.parse().unwrap();
+        let solution = BreachSolver::new(&puzzle)
+            .solve(SolverSearchMethod::Shortest)
+            .unwrap();
+        (puzzle, solution)
+    }
+
+    #[test]
+    fn render_plain() {
+        let (puzzle, solution) = solve(
+            "buffer: 4
+            grid:
+            1C 55 BD
+            55 E9 1C
+            BD 1C 55
+            daemons:
+            1C 55
+            55 E9",
+        );
+        assert_eq!(
+            render_solution(&puzzle, &solution, false),
+            "\
+┌────────┬────────┬────────┐
+│ 1C  1↓ │ 55     │ BD     │
+├────────┼────────┼────────┤
+│ 55  2→ │ E9  3■ │ 1C     │
+├────────┼────────┼────────┤
+│ BD     │ 1C     │ 55     │
+└────────┴────────┴────────┘
+Buffer: 1C 55 E9
+Daemons:
+  #1 1C 55        uploaded at steps 1-2
+  #2 55 E9        uploaded at steps 2-3
+"
+        );
+    }
+
+    #[test]
+    fn render_daemon_types() {
+        let (puzzle, solution) = solve(
+            "buffer: 4
+            grid:
+            1C 55
+            55 BD
+            daemons:
+            1C 55 # datamine_v2",
+        );
+        let text = render_solution(&puzzle, &solution, false);
+        assert!(text.ends_with(
+            "uploaded at steps 1-2 (Datamine V2: medium amount of eurodollars and crafting \
+             components)\n"
+        ));
+    }
+
+    #[test]
+    fn render_partial() {
+        let puzzle: Puzzle = "buffer: 4
+            grid:
+            1C --
+            55 BD
+            daemons:
+            1C
+            55 BD"
+            .parse()
+            .unwrap();
+        let state = PuzzleState {
+            buffer: vec![Code::new(0x1C)],
+            used_cells: vec![(0, 0)],
+            active: PuzzleMove::Row(0),
+        };
+        let solution =
+            BreachSolver::with_state(&puzzle, &state).solve(SolverSearchMethod::Shortest);
+        // The second column of the first row was used too
+        assert!(solution.is_none());
+
+        let state = PuzzleState {
+            active: PuzzleMove::Column(0),
+            ..state
+        };
+        let solution = BreachSolver::with_state(&puzzle, &state)
+            .solve(SolverSearchMethod::Shortest)
+            .unwrap();
+        let text = render_solution(&puzzle, &solution, false);
+        assert!(text.contains("│ 55  1→ │ BD  2■ │"), "{}", text);
+        assert!(
+            text.contains("#1 1C           already uploaded"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("#2 55 BD        uploaded at steps 1-2"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn render_colors() {
+        let (puzzle, solution) = solve(
+            "buffer: 4
+            grid:
+            1C 55
+            55 BD
+            daemons:
+            1C 55",
+        );
+        let text = render_solution(&puzzle, &solution, true);
+        assert!(text.contains(&format!("{} 1C  1↓ {}", DAEMON_COLORS[0], ANSI_RESET)));
+        assert!(text.contains(&format!("{} BD     {}", ANSI_DIM, ANSI_RESET)));
+    }
+}
@@ -0,0 +1,366 @@
+use std::ops::RangeInclusive;
+
+use opencv::core as cv;
+use opencv::imgproc;
+use opencv::prelude::*;
+use serde_json::{json, Value};
+
+use super::{
+    detect_daemons_with, detect_grid_with, scan_buffer, DebugDump, ScanError, Scanner, Thresholds,
+};
+use crate::layout::{find_matrix_header, reference_resolution, FrameScale, PanelLayout};
+use crate::types::Puzzle;
+
+/// Buffer match thresholds tried, in hundredths
+const BUFFER_MATCH_RANGE: RangeInclusive<u32> = 40..=95;
+const BUFFER_MATCH_STEP: usize = 5;
+
+/// Grid and daemon thresholds tried, in hundredths of the contrast
+const DETECT_RANGE: RangeInclusive<u32> = 4..=40;
+const DETECT_STEP: usize = 1;
+
+/// UI scales measured within this ratio of the frame scale are the default UI scale, since the
+/// header height is only measured to the pixel
+const SAME_UI_SCALE: f64 = 0.03;
+
+/// Settings measured on the screenshot of a known puzzle. Rectangles are in the reference
+/// coordinates of the calibrated profile.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Calibration {
+    /// Reference resolution of the profile, which differs from the settings when the game UI is
+    /// scaled, so that the pixel values keep matching the UI at the reference resolution
+    pub reference: cv::Size,
+    pub matrix_header: cv::Rect,
+    pub buffer: cv::Rect,
+    pub grid: cv::Rect,
+    pub daemons: cv::Rect,
+    /// Detected daemon rows, followed by the hinted rows below them
+    pub daemon_rows: Vec<cv::Rect>,
+    pub daemon_names: cv::Rect,
+    pub thresholds: Thresholds,
+}
+
+impl Calibration {
+    /// Calibrated values by settings key
+    pub(crate) fn settings(&self) -> Vec<(String, Value)> {
+        let mut values = vec![
+            (
+                "reference_resolution.width".to_string(),
+                json!(self.reference.width),
+            ),
+            (
+                "reference_resolution.height".to_string(),
+                json!(self.reference.height),
+            ),
+        ];
+        let rects = [
+            ("buffer", self.buffer),
+            ("grid", self.grid),
+            ("daemons", self.daemons),
+            ("daemon_names", self.daemon_names),
+            ("layout.matrix_header", self.matrix_header),
+        ];
+        for (key, rect) in rects {
+            let bounds = [
+                ("left", rect.x),
+                ("right", rect.x + rect.width),
+                ("top", rect.y),
+                ("bottom", rect.y + rect.height),
+            ];
+            for (bound, value) in bounds {
+                values.push((format!("{}.{}", key, bound), json!(value)));
+            }
+        }
+        let rows = self
+            .daemon_rows
+            .iter()
+            .map(|row| json!({ "top": row.y, "bottom": row.y + row.height }))
+            .collect();
+        values.push(("daemons.rows".to_string(), Value::Array(rows)));
+        let thresholds = [
+            ("opencv.detect_grid_threshold", self.thresholds.grid),
+            ("opencv.detect_daemon_threshold", self.thresholds.daemon),
+            (
+                "opencv.buffer_match_threshold",
+                self.thresholds.buffer_match,
+            ),
+        ];
+        for (key, threshold) in thresholds {
+            values.push((key.to_string(), json!(threshold)));
+        }
+        values
+    }
+}
+
+/// Threshold values of a range in hundredths
+fn candidates(range: RangeInclusive<u32>, step: usize) -> Vec<f64> {
+    range
+        .step_by(step)
+        .map(|hundredths| hundredths as f64 / 100.0)
+        .collect()
+}
+
+/// First and last index of the longest run of passed candidates, the first one on ties
+fn longest_run(passed: &[bool]) -> Option<(usize, usize)> {
+    let mut longest: Option<(usize, usize)> = None;
+    let mut start = None;
+    for (i, &pass) in passed.iter().enumerate() {
+        if !pass {
+            start = None;
+            continue;
+        }
+        let first = *start.get_or_insert(i);
+        if longest.map_or(true, |(a, b)| i - first > b - a) {
+            longest = Some((first, i));
+        }
+    }
+    longest
+}
+
+/// Try every candidate threshold and keep the middle of the longest run detecting the expected
+/// result, which is the farthest from the values missing or merging codes. Errors which another
+/// threshold may fix count as a wrong detection.
+fn pick_threshold(
+    name: &str,
+    candidates: &[f64],
+    mut detects_expected: impl FnMut(f64) -> Result<bool, ScanError>,
+) -> Result<f64, ScanError> {
+    let mut passed = Vec::with_capacity(candidates.len());
+    for &candidate in candidates {
+        passed.push(match detects_expected(candidate) {
+            Ok(pass) => pass,
+            Err(error) if error.is_retryable() => false,
+            Err(error) => return Err(error),
+        });
+    }
+    let (first, last) = longest_run(&passed).ok_or_else(|| {
+        ScanError::Calibration(format!(
+            "no {} threshold from {} to {} detects the expected puzzle",
+            name,
+            candidates[0],
+            candidates[candidates.len() - 1]
+        ))
+    })?;
+    Ok(candidates[(first + last) / 2])
+}
+
+impl Scanner {
+    /// Calibrate the settings on a screenshot of an open breach showing the expected puzzle.
+    /// The panel is located from the code matrix header, then each threshold is tried on its
+    /// region. The calibrated thresholds must scan the expected puzzle without any retry, and the
+    /// scan is written to the debug dump.
+    pub(crate) fn calibrate(
+        &mut self,
+        screen: &Mat,
+        expected: &Puzzle,
+        debug: &mut DebugDump,
+    ) -> Result<Calibration, ScanError> {
+        let mut grey = Mat::default();
+        imgproc::cvt_color(&screen, &mut grey, imgproc::COLOR_BGR2GRAY, 0)?;
+        let frame = grey.size()?;
+        let frame_scale = FrameScale::new(frame);
+        let header = find_matrix_header(&grey, &frame_scale).ok_or_else(|| {
+            ScanError::Calibration(
+                "code matrix header not found, the screenshot must show an open breach".to_string(),
+            )
+        })?;
+        let layout = PanelLayout::from_header(header, frame);
+        let scale = &layout.scale;
+
+        let buffer_match = pick_threshold(
+            "buffer match",
+            &candidates(BUFFER_MATCH_RANGE, BUFFER_MATCH_STEP),
+            |threshold| {
+                let (_, size) = scan_buffer(
+                    &mut self.reader,
+                    &grey,
+                    &layout.buffer,
+                    &self.buffer_template,
+                    threshold,
+                    scale,
+                    &mut DebugDump::default(),
+                )?;
+                Ok(size == expected.buffer_size)
+            },
+        )?;
+        let grid = pick_threshold(
+            "code matrix",
+            &candidates(DETECT_RANGE, DETECT_STEP),
+            |fraction| {
+                let info = detect_grid_with(
+                    &grey,
+                    &layout.grid,
+                    fraction,
+                    scale,
+                    &mut DebugDump::default(),
+                )?;
+                Ok((info.rows, info.cols) == (expected.grid.rows, expected.grid.cols))
+            },
+        )?;
+        // Without hint rows, so that the rows must be detected
+        let detect_rows = |fraction| {
+            detect_daemons_with(
+                &grey,
+                &layout.daemons,
+                &[],
+                fraction,
+                scale,
+                &mut DebugDump::default(),
+            )
+        };
+        let expected_lengths: Vec<u32> = expected
+            .daemons
+            .iter()
+            .map(|daemon| daemon.len() as u32)
+            .collect();
+        let daemon = pick_threshold(
+            "daemon",
+            &candidates(DETECT_RANGE, DETECT_STEP),
+            |fraction| {
+                let lengths: Vec<u32> = detect_rows(fraction)?
+                    .iter()
+                    .map(|(_, info)| info.cols)
+                    .collect();
+                Ok(lengths == expected_lengths)
+            },
+        )?;
+        let mut daemon_rows: Vec<cv::Rect> = detect_rows(daemon)?
+            .into_iter()
+            .map(|(row, _)| row)
+            .collect();
+        if let Some(&last) = daemon_rows.last() {
+            let below = layout
+                .daemon_rows
+                .iter()
+                .filter(|row| row.y >= last.y + last.height);
+            daemon_rows.extend(below);
+        }
+
+        // The thresholds of the scanner are kept, the calibrated ones are only checked
+        let thresholds = Thresholds {
+            buffer_match,
+            grid,
+            daemon,
+        };
+        let settings_thresholds = std::mem::replace(&mut self.thresholds, thresholds);
+        let result = self.scan_panel(screen, &grey, &layout, debug);
+        self.thresholds = settings_thresholds;
+        debug.finish(&result);
+        let puzzle = result?.puzzle;
+        if !debug.summary.retries.is_empty() {
+            return Err(ScanError::Calibration(format!(
+                "the calibrated scan needed retries: {}",
+                debug.summary.retries.join("; ")
+            )));
+        }
+        if puzzle.buffer_size != expected.buffer_size
+            || puzzle.grid != expected.grid
+            || puzzle.daemons != expected.daemons
+        {
+            return Err(ScanError::Calibration(format!(
+                "the calibrated scan found another puzzle:\n{}",
+                puzzle
+            )));
+        }
+
+        // Reference resolution where the panel has the size of the settings, so that only the
+        // positions change in the profile
+        let mut ui_ratio = frame_scale.scale() / scale.scale();
+        if (ui_ratio - 1.0).abs() < SAME_UI_SCALE {
+            ui_ratio = 1.0;
+        }
+        let reference = reference_resolution();
+        let reference = cv::Size::new(
+            (reference.width as f64 * ui_ratio).round() as i32,
+            (reference.height as f64 * ui_ratio).round() as i32,
+        );
+        let profile_scale = FrameScale::with_reference(frame, reference);
+        let to_reference = |rect: cv::Rect| profile_scale.reference_rect(rect);
+        Ok(Calibration {
+            reference,
+            matrix_header: to_reference(header),
+            buffer: to_reference(layout.buffer),
+            grid: to_reference(layout.grid),
+            daemons: to_reference(layout.daemons),
+            daemon_rows: daemon_rows.into_iter().map(to_reference).collect(),
+            daemon_names: to_reference(layout.daemon_names),
+            thresholds,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::imgcodecs::{imread, ImreadModes};
+
+    use super::*;
+    use crate::configuration::tests::load_default_settings;
+
+    #[test]
+    fn longest_passed_run() {
+        assert_eq!(longest_run(&[]), None);
+        assert_eq!(longest_run(&[false, false]), None);
+        assert_eq!(longest_run(&[true]), Some((0, 0)));
+        assert_eq!(
+            longest_run(&[false, true, true, false, true, true, true, false]),
+            Some((4, 6))
+        );
+        assert_eq!(longest_run(&[true, true, false, true, true]), Some((0, 1)));
+        assert_eq!(candidates(40..=55, 5), vec![0.4, 0.45, 0.5, 0.55]);
+    }
+
+    #[test]
+    fn calibrate_test_screenshot() {
+        load_default_settings();
+        let screen = imread("test/test_6x6_2.jpg", ImreadModes::IMREAD_UNCHANGED as i32).unwrap();
+        let expected: Puzzle = "buffer: 8
+            grid:
+            E9 55 E9 55 E9 55
+            BD 1C 7A 7A 55 E9
+            1C 55 7A 1C 1C E9
+            7A 1C 7A BD BD 55
+            7A 55 1C E9 55 7A
+            BD 1C 1C 55 55 7A
+            daemons:
+            1C 55 1C
+            1C 1C
+            1C 55 1C"
+            .parse()
+            .unwrap();
+        let mut scanner = Scanner::new().unwrap();
+        let calibration = scanner
+            .calibrate(&screen, &expected, &mut DebugDump::default())
+            .unwrap();
+        // The screenshot has the default UI scale at the reference resolution
+        assert_eq!(calibration.reference, cv::Size::new(1920, 1080));
+        assert_eq!(calibration.daemon_rows.len(), 4);
+        let near = |a: cv::Rect, b: cv::Rect| {
+            (a.x - b.x).abs() <= 10
+                && (a.y - b.y).abs() <= 10
+                && (a.width - b.width).abs() <= 3
+                && (a.height - b.height).abs() <= 3
+        };
+        let settings = PanelLayout::from_settings(FrameScale::new(screen.size().unwrap()));
+        assert!(near(calibration.grid, settings.grid), "{:?}", calibration);
+        assert!(
+            near(calibration.buffer, settings.buffer),
+            "{:?}",
+            calibration
+        );
+        let thresholds = calibration.thresholds;
+        assert!((0.04..=0.40).contains(&thresholds.grid), "{:?}", thresholds);
+        assert!(
+            (0.04..=0.40).contains(&thresholds.daemon),
+            "{:?}",
+            thresholds
+        );
+
+        // Another puzzle cannot be calibrated on the screenshot
+        let mut other = expected.clone();
+        other.grid.set_cell(0, 0, "1C".parse().unwrap());
+        assert!(matches!(
+            scanner.calibrate(&screen, &other, &mut DebugDump::default()),
+            Err(ScanError::Calibration(_))
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+use cv::Mat;
+use opencv::core as cv;
+use opencv::imgproc;
+use opencv::prelude::*;
+
+use super::ScanError;
+use crate::configuration::settings;
+
+/// Number of blocks of the panel hash along each side
+const HASH_SIZE: i32 = 32;
+
+/// Perceptual hash of the breach panel: its mean grey level in each block of a grid.
+/// Capture noise and small brightness changes average out in a block, while a code appearing or
+/// disappearing changes the level of the blocks around it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PanelHash {
+    levels: Vec<u8>,
+}
+
+impl PanelHash {
+    /// Hash of a region of a greyscale or colour frame, with the ignored areas blacked out
+    pub(crate) fn new(
+        screen: &Mat,
+        rect: &cv::Rect,
+        ignored: &[cv::Rect],
+    ) -> Result<PanelHash, ScanError> {
+        let roi = Mat::roi(screen, *rect)?;
+        let mut grey = Mat::default();
+        if roi.channels()? == 1 {
+            roi.copy_to(&mut grey)?;
+        } else {
+            imgproc::cvt_color(&roi, &mut grey, imgproc::COLOR_BGR2GRAY, 0)?;
+        }
+        for area in ignored {
+            // Intersection with the region, in region coordinates
+            let mut area = *area & *rect;
+            if area.area() > 0 {
+                area.x -= rect.x;
+                area.y -= rect.y;
+                Mat::roi(&grey, area)?.set_to(&cv::Scalar::all(0.0), &cv::no_array()?)?;
+            }
+        }
+        let mut blocks = Mat::default();
+        imgproc::resize(
+            &grey,
+            &mut blocks,
+            cv::Size::new(HASH_SIZE, HASH_SIZE),
+            0.0,
+            0.0,
+            imgproc::INTER_AREA,
+        )?;
+        Ok(PanelHash {
+            levels: blocks.data_typed::<u8>()?.to_vec(),
+        })
+    }
+
+    /// Largest level difference between the blocks of two hashes
+    pub(crate) fn distance(&self, other: &PanelHash) -> u8 {
+        self.levels
+            .iter()
+            .zip(other.levels.iter())
+            .map(|(&a, &b)| a.max(b) - a.min(b))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Detects changes of the breach panel between captured frames, so that only new or changed
+/// puzzles are scanned
+pub(crate) struct ChangeDetector {
+    /// Panel area of the last scanned puzzle, with its hash
+    panel: Option<(cv::Rect, PanelHash)>,
+    /// Screen areas left out of the hash, such as the overlay drawn on top of the panel
+    ignored: Vec<cv::Rect>,
+    /// Smallest block level difference counted as a change
+    change_level: u8,
+}
+
+impl ChangeDetector {
+    /// Detector with the `watch.change_level` setting
+    pub(crate) fn new() -> ChangeDetector {
+        ChangeDetector {
+            panel: None,
+            ignored: Vec::new(),
+            change_level: settings().watch.change_level,
+        }
+    }
+
+    /// Leave screen areas out of the comparison instead of the previous ones, e.g. where the
+    /// overlay is shown
+    pub(crate) fn ignore(&mut self, areas: impl IntoIterator<Item = cv::Rect>) {
+        self.ignored = areas.into_iter().collect();
+    }
+
+    /// Watch the panel area of a puzzle scanned from the frame
+    pub(crate) fn watch(&mut self, screen: &Mat, panel: &cv::Rect) -> Result<(), ScanError> {
+        self.panel = Some((*panel, PanelHash::new(screen, panel, &self.ignored)?));
+        Ok(())
+    }
+
+    /// Stop watching the panel, so that the next frame is reported as changed
+    pub(crate) fn reset(&mut self) {
+        self.panel = None;
+    }
+
+    /// Check if the frame should be scanned: no panel is watched, the frame is smaller than the
+    /// watched panel area, or the panel looks different
+    pub(crate) fn changed(&self, screen: &Mat) -> Result<bool, ScanError> {
+        let (panel, hash) = match &self.panel {
+            Some(watched) => watched,
+            None => return Ok(true),
+        };
+        let frame = screen.size()?;
+        if panel.x + panel.width > frame.width || panel.y + panel.height > frame.height {
+            return Ok(true);
+        }
+        let current = PanelHash::new(screen, panel, &self.ignored)?;
+        Ok(current.distance(hash) >= self.change_level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::imgcodecs::{imread, ImreadModes};
+
+    use super::super::median_grey;
+    use super::*;
+
+    #[test]
+    fn detect_panel_changes() {
+        let screen = imread("test/test_6x6.png", ImreadModes::IMREAD_COLOR as i32).unwrap();
+        let panel = cv::Rect::new(305, 190, 915, 710);
+        let mut detector = ChangeDetector {
+            panel: None,
+            ignored: Vec::new(),
+            change_level: 12,
+        };
+        assert!(detector.changed(&screen).unwrap());
+        detector.watch(&screen, &panel).unwrap();
+        assert!(!detector.changed(&screen).unwrap());
+
+        // Slightly brighter frame
+        let mut brighter = Mat::default();
+        screen.convert_to(&mut brighter, -1, 1.0, 6.0).unwrap();
+        assert!(!detector.changed(&brighter).unwrap());
+
+        // A code of the matrix is selected, so its cell is blank
+        let mut selected = screen.clone();
+        let background = cv::Scalar::all(median_level(&screen, cv::Rect::new(310, 360, 20, 10)));
+        let mut cell = Mat::roi(&selected, cv::Rect::new(320, 365, 45, 40)).unwrap();
+        cell.set_to(&background, &cv::no_array().unwrap()).unwrap();
+        assert!(detector.changed(&selected).unwrap());
+        // unless it is in an ignored area
+        let mut ignoring = ChangeDetector {
+            panel: None,
+            ignored: vec![cv::Rect::new(300, 350, 100, 100)],
+            change_level: 12,
+        };
+        ignoring.watch(&screen, &panel).unwrap();
+        assert!(!ignoring.changed(&selected).unwrap());
+
+        // Another puzzle, and the puzzle closed
+        let other = imread("test/test_6x6_2.jpg", ImreadModes::IMREAD_COLOR as i32).unwrap();
+        assert!(detector.changed(&other).unwrap());
+        let closed = Mat::new_rows_cols_with_default(
+            screen.rows(),
+            screen.cols(),
+            screen.typ().unwrap(),
+            cv::Scalar::all(0.0),
+        )
+        .unwrap();
+        assert!(detector.changed(&closed).unwrap());
+
+        detector.reset();
+        assert!(detector.changed(&screen).unwrap());
+    }
+
+    fn median_level(screen: &Mat, rect: cv::Rect) -> f64 {
+        let mut grey = Mat::default();
+        imgproc::cvt_color(screen, &mut grey, imgproc::COLOR_BGR2GRAY, 0).unwrap();
+        median_grey(&grey, &rect).unwrap() as f64
+    }
+}
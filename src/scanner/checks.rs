@@ -0,0 +1,191 @@
+use opencv::core as cv;
+
+use super::{DebugDump, ScanError};
+use crate::configuration::{settings, ChecksCfg};
+use crate::types::{Cell, Code, PuzzleGrid};
+
+/// Factors applied to a detection threshold when the first scan of a region fails its checks,
+/// first lowering it to find faint regions, then raising it to split merged ones
+const RETRY_THRESHOLD_FACTORS: [f64; 2] = [0.8, 1.25];
+
+/// Alternate crops and filters of a cell image, tried in order when its code cannot be read
+pub(super) const RETRY_PREPROCESS: [CellPreprocess; 3] = [
+    CellPreprocess::Grow(4),
+    CellPreprocess::Shrink(3),
+    CellPreprocess::Blur,
+];
+
+/// Preprocessing of a cell image before OCR
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum CellPreprocess {
+    /// Cell as detected
+    Plain,
+    /// Cell with a margin of the given pixels at the reference resolution on each side,
+    /// for glyphs cut by a tight cell
+    Grow(i32),
+    /// Cell without the given pixels at the reference resolution on each side,
+    /// for cells catching the border of a neighbour
+    Shrink(i32),
+    /// Cell smoothed with a small gaussian blur, for noisy or compressed frames
+    Blur,
+}
+
+/// Consistency checks of the scanned puzzle against the sizes of the game, from the `checks`
+/// settings
+pub(super) struct ScanChecks {
+    cfg: ChecksCfg,
+}
+
+impl ScanChecks {
+    pub(super) fn from_settings() -> ScanChecks {
+        ScanChecks {
+            cfg: settings().checks.clone(),
+        }
+    }
+
+    pub(super) fn check_buffer_size(&self, size: u32, rect: &cv::Rect) -> Result<(), ScanError> {
+        if size == 0 {
+            return Err(ScanError::BufferNotFound { rect: *rect });
+        }
+        if size < self.cfg.min_buffer_size || size > self.cfg.max_buffer_size {
+            return Err(ScanError::UnexpectedBufferSize { size, rect: *rect });
+        }
+        Ok(())
+    }
+
+    /// Check that the grid is square with a size in the game range, or of one of the listed
+    /// sizes
+    pub(super) fn check_grid_size(
+        &self,
+        rows: u32,
+        cols: u32,
+        rect: &cv::Rect,
+    ) -> Result<(), ScanError> {
+        let square =
+            rows == cols && rows >= self.cfg.min_grid_size && rows <= self.cfg.max_grid_size;
+        if square || self.cfg.grid_sizes.contains(&[rows, cols]) {
+            Ok(())
+        } else {
+            Err(ScanError::UnexpectedGridSize {
+                rows,
+                cols,
+                rect: *rect,
+            })
+        }
+    }
+}
+
+/// Check if a daemon code may be part of the grid. Codes of used cells are not shown, so any
+/// code may have been there.
+pub(super) fn grid_may_contain(grid: &PuzzleGrid, code: &Code) -> bool {
+    grid.cells
+        .iter()
+        .any(|cell| cell == code || *cell == Cell::Used)
+}
+
+/// Run a detection with its threshold scaled by 1, then by each of the retry factors until its
+/// result passes the check. Only errors which another threshold may fix are retried, the error of
+/// the first attempt is returned when all of them fail.
+pub(super) fn retry_thresholds<T>(
+    name: &str,
+    debug: &mut DebugDump,
+    mut detect: impl FnMut(f64, &mut DebugDump) -> Result<T, ScanError>,
+    check: impl Fn(&T) -> Result<(), ScanError>,
+) -> Result<T, ScanError> {
+    let mut first_error = None;
+    for factor in std::iter::once(1.0).chain(RETRY_THRESHOLD_FACTORS) {
+        let error = match detect(factor, debug).and_then(|result| check(&result).map(|_| result)) {
+            Ok(result) => return Ok(result),
+            Err(error) if error.is_retryable() => error,
+            Err(error) => return Err(error),
+        };
+        let retry = format!("{} with threshold x{}: {}", name, factor, error);
+        debug.summary.retries.push(retry);
+        first_error.get_or_insert(error);
+    }
+    Err(first_error.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checks() -> ScanChecks {
+        ScanChecks {
+            cfg: ChecksCfg {
+                min_buffer_size: 4,
+                max_buffer_size: 8,
+                min_grid_size: 5,
+                max_grid_size: 7,
+                grid_sizes: vec![[5, 6]],
+            },
+        }
+    }
+
+    #[test]
+    fn check_sizes() {
+        let checks = checks();
+        let rect = cv::Rect::new(0, 0, 10, 10);
+        assert!(checks.check_buffer_size(4, &rect).is_ok());
+        assert!(checks.check_buffer_size(8, &rect).is_ok());
+        assert!(matches!(
+            checks.check_buffer_size(0, &rect),
+            Err(ScanError::BufferNotFound { .. })
+        ));
+        assert!(matches!(
+            checks.check_buffer_size(12, &rect),
+            Err(ScanError::UnexpectedBufferSize { size: 12, .. })
+        ));
+        assert!(checks.check_grid_size(5, 5, &rect).is_ok());
+        assert!(checks.check_grid_size(7, 7, &rect).is_ok());
+        assert!(checks.check_grid_size(5, 6, &rect).is_ok());
+        assert!(matches!(
+            checks.check_grid_size(6, 5, &rect),
+            Err(ScanError::UnexpectedGridSize {
+                rows: 6,
+                cols: 5,
+                ..
+            })
+        ));
+        assert!(checks.check_grid_size(4, 4, &rect).is_err());
+        assert!(checks.check_grid_size(8, 8, &rect).is_err());
+    }
+
+    #[test]
+    fn retry_until_checked() {
+        let mut debug = DebugDump::default();
+        let rect = cv::Rect::new(0, 0, 10, 10);
+        // The detection only passes with the raised threshold
+        let size = retry_thresholds(
+            "buffer",
+            &mut debug,
+            |factor, _| Ok(if factor > 1.0 { 6 } else { 12 }),
+            |&size| checks().check_buffer_size(size, &rect),
+        );
+        assert_eq!(size.unwrap(), 6);
+        assert_eq!(debug.summary.retries.len(), 2);
+
+        // Errors which are not about the scanned region are not retried
+        let mut attempts = 0;
+        let result: Result<u32, _> = retry_thresholds(
+            "buffer",
+            &mut debug,
+            |_, _| {
+                attempts += 1;
+                Err(ScanError::MissingAsset("buffer.png".to_string()))
+            },
+            |_| Ok(()),
+        );
+        assert!(matches!(result, Err(ScanError::MissingAsset(_))));
+        assert_eq!(attempts, 1);
+
+        // The error of the first attempt is reported
+        let result = retry_thresholds(
+            "buffer",
+            &mut debug,
+            |factor, _| Ok(if factor == 1.0 { 0 } else { 12 }),
+            |&size| checks().check_buffer_size(size, &rect),
+        );
+        assert!(matches!(result, Err(ScanError::BufferNotFound { .. })));
+    }
+}
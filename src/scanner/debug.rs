@@ -0,0 +1,197 @@
+use opencv::core as cv;
+use opencv::imgcodecs::imwrite;
+use opencv::imgproc;
+use opencv::prelude::*;
+use serde::Serialize;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{ScanError, ScanResult};
+
+/// Rectangle in screen coordinates
+#[derive(Debug, Clone, Copy, Serialize)]
+pub(crate) struct RectSummary {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl From<cv::Rect> for RectSummary {
+    fn from(rect: cv::Rect) -> Self {
+        RectSummary {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+        }
+    }
+}
+
+/// Recognition of a single grid or daemon cell
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct CellSummary {
+    pub name: String,
+    pub rect: RectSummary,
+    /// Text as read by the OCR engine
+    pub text: String,
+    /// Code after corrections, if valid
+    pub code: Option<String>,
+    pub confidence: u8,
+}
+
+/// Values detected at each scan stage, written as `summary.json`
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct ScanSummary {
+    pub frame_width: i32,
+    pub frame_height: i32,
+    pub scale: f64,
+    /// The panel was not located and the layout from the settings was used
+    pub layout_from_settings: bool,
+    pub buffer_rect: Option<RectSummary>,
+    pub grid_rect: Option<RectSummary>,
+    pub daemon_panel: Option<RectSummary>,
+    /// Detected daemon rows holding a sequence
+    pub daemon_rows: Vec<RectSummary>,
+    /// No sequence row was detected and the rows from the settings were used
+    pub daemon_rows_from_settings: bool,
+    /// Daemon names as read by the OCR engine
+    pub daemon_names: Vec<String>,
+    /// Daemon types matched with the names
+    pub daemon_types: Vec<String>,
+    pub buffer_size: Option<u32>,
+    /// Codes already in the buffer
+    pub buffer_codes: Vec<String>,
+    pub grid_rows: Option<u32>,
+    pub grid_cols: Option<u32>,
+    pub cells: Vec<CellSummary>,
+    /// Grid cells shown as already selected, as (row, column)
+    pub used_cells: Vec<(u32, u32)>,
+    /// Highlighted row or column, if any
+    pub active_line: Option<String>,
+    /// Scanned puzzle in text format
+    pub puzzle: Option<String>,
+    pub warnings: Vec<String>,
+    /// Regions scanned again after failing a consistency check, with the reason
+    pub retries: Vec<String>,
+    pub error: Option<String>,
+    /// Images written to the folder, in stage order
+    pub images: Vec<String>,
+}
+
+/// Writes annotated images of the scan stages and a JSON summary to a folder.
+/// The default dump is disabled, stages are still summarized but nothing is written.
+/// Failures to write are reported and never fail the scan.
+#[derive(Default)]
+pub(crate) struct DebugDump {
+    dir: Option<PathBuf>,
+    pub summary: ScanSummary,
+}
+
+impl DebugDump {
+    pub(crate) fn new(dir: &Path) -> Result<DebugDump, ScanError> {
+        fs::create_dir_all(dir).map_err(|e| {
+            ScanError::Config(format!("cannot create debug dir {}: {}", dir.display(), e))
+        })?;
+        Ok(DebugDump {
+            dir: Some(dir.to_path_buf()),
+            summary: ScanSummary::default(),
+        })
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Write an image as `<name>.png`, images which are not 8 bit are stretched to 0-255
+    pub(crate) fn image(&mut self, name: &str, image: &Mat) {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return,
+        };
+        let file_name = format!("{}.png", name);
+        let path = dir.join(&file_name);
+        let written = to_8bit(image).and_then(|image| {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).ok();
+            }
+            imwrite(&path.to_string_lossy(), &image, &cv::Vector::new())
+        });
+        match written {
+            Ok(true) => self.summary.images.push(file_name),
+            Ok(false) => println!("Failed to write debug image {}", path.display()),
+            Err(error) => println!("Failed to write debug image {}: {}", path.display(), error),
+        }
+    }
+
+    /// Write an image with the rectangles drawn on top
+    pub(crate) fn rects(&mut self, name: &str, image: &Mat, rects: &[cv::Rect]) {
+        if !self.enabled() {
+            return;
+        }
+        match draw_rects(image, rects) {
+            Ok(annotated) => self.image(name, &annotated),
+            Err(error) => println!("Failed to annotate debug image {}: {}", name, error),
+        }
+    }
+
+    /// Record the scan outcome and write the summary
+    pub(crate) fn finish(&mut self, result: &Result<ScanResult, ScanError>) {
+        match result {
+            Ok(result) => {
+                self.summary.puzzle = Some(result.puzzle.to_string());
+                self.summary.warnings = result.warnings();
+            }
+            Err(error) => self.summary.error = Some(error.to_string()),
+        }
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return,
+        };
+        let path = dir.join("summary.json");
+        let written = serde_json::to_string_pretty(&self.summary)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+        if let Err(error) = written {
+            println!(
+                "Failed to write debug summary {}: {}",
+                path.display(),
+                error
+            );
+        }
+    }
+}
+
+fn to_8bit(image: &Mat) -> opencv::Result<Mat> {
+    let mut converted = Mat::default();
+    if image.depth()? == cv::CV_8U {
+        image.copy_to(&mut converted)?;
+    } else {
+        let no_mask = cv::no_array()?;
+        cv::normalize(
+            image,
+            &mut converted,
+            0.0,
+            255.0,
+            cv::NORM_MINMAX,
+            cv::CV_8U,
+            &no_mask,
+        )?;
+    }
+    Ok(converted)
+}
+
+fn draw_rects(image: &Mat, rects: &[cv::Rect]) -> opencv::Result<Mat> {
+    let green = cv::Scalar::new(0.0, 255.0, 0.0, 255.0);
+    let mut annotated = Mat::default();
+    if image.channels()? == 1 {
+        imgproc::cvt_color(&to_8bit(image)?, &mut annotated, imgproc::COLOR_GRAY2BGR, 0)?;
+    } else {
+        image.copy_to(&mut annotated)?;
+    }
+    for rect in rects {
+        imgproc::rectangle(&mut annotated, *rect, green, 2, imgproc::LINE_8, 0)?;
+    }
+    Ok(annotated)
+}
@@ -0,0 +1,127 @@
+use opencv::core as cv;
+use std::fmt;
+
+use crate::types::PuzzleError;
+
+/// Failure while scanning a breach protocol screen.
+/// Region variants carry the screen rectangle that was inspected, to help when tuning the layout.
+#[derive(Debug)]
+pub(crate) enum ScanError {
+    /// The screen could not be captured
+    Capture(String),
+    /// No buffer slot was found in the buffer region
+    BufferNotFound { rect: cv::Rect },
+    /// The buffer size is out of the range of the game
+    UnexpectedBufferSize { size: u32, rect: cv::Rect },
+    /// No rows or columns were found in the code matrix region
+    GridNotFound { rect: cv::Rect },
+    /// The code matrix is neither square nor of a size listed in the settings
+    UnexpectedGridSize {
+        rows: u32,
+        cols: u32,
+        rect: cv::Rect,
+    },
+    /// A grid cell was recognized as text which is not a valid code
+    InvalidCode { cell: cv::Rect, text: String },
+    /// A cell of a daemon sequence row was recognized as text which is not a valid code
+    DaemonRowUnreadable { rect: cv::Rect, text: String },
+    /// The recognized codes do not form a valid puzzle
+    InvalidPuzzle(PuzzleError),
+    /// A required asset file is missing or unreadable
+    MissingAsset(String),
+    /// A setting has an invalid value
+    Config(String),
+    /// No settings make the screenshot of a known puzzle scan as expected
+    Calibration(String),
+    /// The OCR engine failed
+    Ocr(String),
+    /// An OpenCV operation failed
+    Backend(opencv::Error),
+}
+
+impl ScanError {
+    /// Check if the error comes from what was detected or read in a region, so that scanning it
+    /// again with other parameters may succeed
+    pub(crate) fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ScanError::BufferNotFound { .. }
+                | ScanError::UnexpectedBufferSize { .. }
+                | ScanError::GridNotFound { .. }
+                | ScanError::UnexpectedGridSize { .. }
+                | ScanError::InvalidCode { .. }
+                | ScanError::DaemonRowUnreadable { .. }
+        )
+    }
+}
+
+fn fmt_rect(rect: &cv::Rect) -> String {
+    format!("({}, {}) {}x{}", rect.x, rect.y, rect.width, rect.height)
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScanError::Capture(msg) => write!(f, "failed to capture screen: {}", msg),
+            ScanError::BufferNotFound { rect } => {
+                write!(f, "no buffer found in region {}", fmt_rect(rect))
+            }
+            ScanError::UnexpectedBufferSize { size, rect } => write!(
+                f,
+                "unexpected buffer size {} found in region {}",
+                size,
+                fmt_rect(rect)
+            ),
+            ScanError::GridNotFound { rect } => {
+                write!(f, "no code matrix found in region {}", fmt_rect(rect))
+            }
+            ScanError::UnexpectedGridSize { rows, cols, rect } => write!(
+                f,
+                "unexpected {}x{} code matrix found in region {}",
+                rows,
+                cols,
+                fmt_rect(rect)
+            ),
+            ScanError::InvalidCode { cell, text } => write!(
+                f,
+                "invalid code \"{}\" recognized in cell {}",
+                text,
+                fmt_rect(cell)
+            ),
+            ScanError::DaemonRowUnreadable { rect, text } => write!(
+                f,
+                "unreadable daemon row {}, recognized \"{}\"",
+                fmt_rect(rect),
+                text
+            ),
+            ScanError::InvalidPuzzle(error) => write!(f, "invalid puzzle: {}", error),
+            ScanError::MissingAsset(path) => write!(f, "asset {} not found", path),
+            ScanError::Config(msg) => write!(f, "invalid settings: {}", msg),
+            ScanError::Calibration(msg) => write!(f, "calibration failed: {}", msg),
+            ScanError::Ocr(msg) => write!(f, "OCR failed: {}", msg),
+            ScanError::Backend(error) => write!(f, "OpenCV error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ScanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScanError::InvalidPuzzle(error) => Some(error),
+            ScanError::Backend(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<opencv::Error> for ScanError {
+    fn from(error: opencv::Error) -> Self {
+        ScanError::Backend(error)
+    }
+}
+
+impl From<PuzzleError> for ScanError {
+    fn from(error: PuzzleError) -> Self {
+        ScanError::InvalidPuzzle(error)
+    }
+}
@@ -0,0 +1,135 @@
+use cv::Mat;
+use opencv::core as cv;
+use opencv::imgproc;
+use opencv::prelude::*;
+
+use super::ScanError;
+use crate::layout::FrameScale;
+
+/// Size of the background details kept by `threshold_blobs` at the reference resolution.
+/// Blurred codes are smaller, highlighted rows and columns of the matrix are larger.
+const BACKGROUND_SIZE: i32 = 55;
+
+/// Grey levels of an image region, measured from its histogram
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Levels {
+    /// Median level, which is the panel background since text covers a small part of it
+    pub background: f64,
+    /// Level of the brightest pixels, i.e. of the text
+    pub text: f64,
+}
+
+impl Levels {
+    pub(crate) fn measure(grey: &Mat) -> Result<Levels, ScanError> {
+        let histogram = histogram(grey)?;
+        Ok(Levels {
+            background: percentile(&histogram, 0.5) as f64,
+            text: percentile(&histogram, 0.999) as f64,
+        })
+    }
+
+    /// Difference between the text and background levels, at least 1
+    pub(crate) fn contrast(&self) -> f64 {
+        (self.text - self.background).max(1.0)
+    }
+
+    /// Level at a fraction of the way from the background to the text
+    pub(crate) fn level(&self, fraction: f64) -> f64 {
+        self.background + fraction * self.contrast()
+    }
+}
+
+/// Number of pixels of each grey level of an 8 bit image
+fn histogram(grey: &Mat) -> Result<[u64; 256], ScanError> {
+    let mut histogram = [0; 256];
+    for y in 0..grey.rows() {
+        // A single row is continuous, even in a region of a larger image
+        let row = grey.row(y)?;
+        for &value in row.data_typed::<u8>()? {
+            histogram[value as usize] += 1;
+        }
+    }
+    Ok(histogram)
+}
+
+/// Lowest level with at least the given fraction of the pixels at or below it
+fn percentile(histogram: &[u64; 256], fraction: f64) -> u8 {
+    let total: u64 = histogram.iter().sum();
+    let target = (total as f64 * fraction).ceil().max(1.0) as u64;
+    let mut count = 0;
+    for (level, &pixels) in histogram.iter().enumerate() {
+        count += pixels;
+        if count >= target {
+            return level as u8;
+        }
+    }
+    0
+}
+
+/// Median grey level of an 8 bit image
+pub(super) fn median(grey: &Mat) -> Result<u8, ScanError> {
+    Ok(percentile(&histogram(grey)?, 0.5))
+}
+
+/// Binary image of the blurred codes, which exceed the local background by a fraction of the
+/// contrast. The local background is the blurred image without details smaller than the codes,
+/// so highlighted rows and columns are not taken for codes whatever their brightness.
+pub(super) fn threshold_blobs(
+    blur: &Mat,
+    levels: &Levels,
+    fraction: f64,
+    scale: &FrameScale,
+) -> Result<Mat, ScanError> {
+    let size = scale.length(BACKGROUND_SIZE);
+    let anchor = cv::Point::new(-1, -1);
+    let kernel =
+        imgproc::get_structuring_element(imgproc::MORPH_RECT, cv::Size::new(size, size), anchor)?;
+    // Top hat: the image minus its local background
+    let mut top_hat = Mat::default();
+    imgproc::morphology_ex(
+        blur,
+        &mut top_hat,
+        imgproc::MORPH_TOPHAT,
+        &kernel,
+        anchor,
+        1,
+        cv::BORDER_CONSTANT,
+        imgproc::morphology_default_border_value()?,
+    )?;
+    let mut thr_img = Mat::default();
+    imgproc::threshold(
+        &top_hat,
+        &mut thr_img,
+        fraction * levels.contrast(),
+        255.0,
+        imgproc::THRESH_BINARY,
+    )?;
+    Ok(thr_img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measure_levels() {
+        // Background with a bright line of text covering less than half of the image
+        let image =
+            Mat::new_rows_cols_with_default(100, 100, cv::CV_8UC1, cv::Scalar::all(20.0)).unwrap();
+        let mut text = Mat::roi(&image, cv::Rect::new(10, 40, 80, 10)).unwrap();
+        text.set_to(&cv::Scalar::all(220.0), &cv::no_array().unwrap())
+            .unwrap();
+        let levels = Levels::measure(&image).unwrap();
+        assert_eq!(
+            levels,
+            Levels {
+                background: 20.0,
+                text: 220.0
+            }
+        );
+        assert_eq!(levels.level(0.25), 70.0);
+        let text_region = Mat::roi(&image, cv::Rect::new(0, 40, 100, 10)).unwrap();
+        assert_eq!(median(&text_region).unwrap(), 220);
+        assert_eq!(median(&Mat::default()).unwrap(), 0);
+    }
+}
@@ -0,0 +1,2403 @@
+mod calibrate;
+mod change;
+mod checks;
+mod debug;
+mod error;
+mod levels;
+mod names;
+
+use cv::Mat;
+use opencv::core as cv;
+use opencv::highgui;
+use opencv::imgcodecs::{imread, ImreadModes};
+use opencv::imgproc;
+use opencv::prelude::*;
+
+use std::thread;
+
+use crate::configuration::settings;
+use crate::layout::{clamp_rect, FrameScale, PanelLayout};
+use crate::ocr::{self, CellReading, CellRecognizer, OcrPool, OcrText, TextRecognizer};
+use crate::types::*;
+use checks::{CellPreprocess, ScanChecks, RETRY_PREPROCESS};
+use debug::CellSummary;
+use names::DaemonNames;
+
+pub(crate) use change::ChangeDetector;
+pub(crate) use debug::DebugDump;
+pub(crate) use error::ScanError;
+pub(crate) use levels::Levels;
+
+// Debug functions
+#[allow(dead_code)]
+pub(crate) fn debug_show(name: &str, mat: &Mat) {
+    let wait_time = 3000; // delay ms
+    highgui::named_window(
+        name,
+        highgui::WINDOW_AUTOSIZE, /* highgui::WINDOW_NORMAL |  highgui::WINDOW_KEEPRATIO */
+    )
+    .unwrap();
+    highgui::imshow(name, mat).unwrap();
+    highgui::wait_key(wait_time).unwrap();
+    highgui::destroy_window(name).unwrap();
+}
+
+#[allow(dead_code)]
+fn debug_image() -> Result<Mat, String> {
+    let screen = imread("test/test_6x6.png", ImreadModes::IMREAD_UNCHANGED as i32)
+        .expect("File test_6x6.png not found");
+    Ok(screen)
+}
+
+#[allow(dead_code)]
+fn debug_contours(img: &Mat, rects: &Vec<cv::Rect>) {
+    let green_rgba = cv::Scalar::new(0.0, 255.0, 0.0, 255.0);
+    let mut contours = Mat::default();
+    imgproc::cvt_color(&img, &mut contours, imgproc::COLOR_GRAY2RGBA, 0).unwrap();
+    for rect in rects {
+        imgproc::rectangle(
+            &mut contours,
+            rect.to_owned(),
+            green_rgba,
+            2,
+            imgproc::FILLED,
+            0,
+        )
+        .unwrap();
+    }
+    // Draw debug contours
+    debug_show("contours", &contours);
+}
+
+/// Puzzle recognized on screen, with the recognition confidence of each code in percent
+#[derive(Debug, Clone)]
+pub(crate) struct ScanResult {
+    pub puzzle: Puzzle,
+    pub grid_confidence: PuzzleGrid<u8>,
+    pub daemon_confidence: Vec<Vec<u8>>,
+    /// Codes recognized with a lower confidence are reported as uncertain
+    pub min_confidence: u8,
+    /// Progress of a partially played puzzle
+    pub state: PuzzleState,
+    /// Breach panel area with the buffer, code matrix and daemons, in screen coordinates
+    pub panel: cv::Rect,
+    /// Code matrix cells area, in screen coordinates
+    pub grid_area: cv::Rect,
+}
+
+impl ScanResult {
+    /// Grid cells recognized with low confidence
+    pub(crate) fn uncertain_cells(&self) -> Vec<CellCoord> {
+        let grid = &self.grid_confidence;
+        (0..grid.rows)
+            .flat_map(|row| (0..grid.cols).map(move |col| (row, col)))
+            .filter(|&(row, col)| *grid.get_cell(row, col) < self.min_confidence)
+            .collect()
+    }
+
+    /// Daemon codes recognized with low confidence, as (daemon, position) pairs
+    pub(crate) fn uncertain_daemon_codes(&self) -> Vec<(usize, usize)> {
+        self.daemon_confidence
+            .iter()
+            .enumerate()
+            .flat_map(|(daemon, confidence)| {
+                confidence
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &value)| value < self.min_confidence)
+                    .map(move |(position, _)| (daemon, position))
+            })
+            .collect()
+    }
+
+    /// Warning lines for the codes recognized with low confidence
+    pub(crate) fn warnings(&self) -> Vec<String> {
+        let grid_warnings = self.uncertain_cells().into_iter().map(|(row, col)| {
+            format!(
+                "Uncertain grid cell at row {}, column {}: {} ({}%)",
+                row + 1,
+                col + 1,
+                self.puzzle.grid.get_cell(row, col),
+                self.grid_confidence.get_cell(row, col)
+            )
+        });
+        let daemon_warnings =
+            self.uncertain_daemon_codes()
+                .into_iter()
+                .map(|(daemon, position)| {
+                    format!(
+                        "Uncertain code {} of daemon #{}: {} ({}%)",
+                        position + 1,
+                        daemon + 1,
+                        self.puzzle.daemons[daemon][position],
+                        self.daemon_confidence[daemon][position]
+                    )
+                });
+        grid_warnings.chain(daemon_warnings).collect()
+    }
+}
+
+/// Image of an empty buffer slot, captured at the reference resolution
+const BUFFER_TEMPLATE: &str = "assets/images/buffer.png";
+
+/// Corrections of the most common OCR mistakes
+const OCR_CORRECTIONS: [(&str, &str); 4] = [("BO", "BD"), ("C", "1C"), ("1CC", "1C"), ("TA", "7A")];
+
+/// Cell OCR engines, reading on the calling thread or on a pool of worker threads
+enum CellEngines {
+    Local(Box<dyn CellRecognizer>),
+    Pool(OcrPool),
+}
+
+/// Cell OCR engines, with the valid codes their readings are checked against
+pub(crate) struct CellReader {
+    engines: CellEngines,
+    valid_codes: Vec<Code>,
+}
+
+impl CellReader {
+    /// Reader of the codes from the `valid_codes` setting with a single engine
+    pub(crate) fn new(ocr: Box<dyn CellRecognizer>) -> Result<CellReader, ScanError> {
+        Ok(CellReader {
+            engines: CellEngines::Local(ocr),
+            valid_codes: settings().valid_codes.clone(),
+        })
+    }
+
+    /// Reader with an engine on each of the `opencv.ocr_threads` worker threads, reading the cells
+    /// of the code matrix in parallel. 0 threads is one per CPU, 1 thread reads on the calling
+    /// thread.
+    pub(crate) fn from_settings() -> Result<CellReader, ScanError> {
+        let threads = match settings().opencv.ocr_threads {
+            0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads,
+        };
+        if threads == 1 {
+            return CellReader::new(ocr::recognizer()?);
+        }
+        Ok(CellReader {
+            engines: CellEngines::Pool(OcrPool::new(threads, ocr::recognizer)?),
+            valid_codes: settings().valid_codes.clone(),
+        })
+    }
+
+    /// Read a cell image, along with the image preprocessed by the engine when asked for
+    fn recognize(&mut self, cell: &Mat, preprocess: bool) -> Result<CellReading, ScanError> {
+        match &mut self.engines {
+            CellEngines::Local(ocr) => ocr::read_cell(ocr.as_mut(), cell, preprocess),
+            CellEngines::Pool(pool) => Ok(pool
+                .recognize_cells(vec![cell.clone()], preprocess)?
+                .remove(0)),
+        }
+    }
+
+    /// Read cell images in order, in parallel when there is a pool of engines
+    fn recognize_all(
+        &mut self,
+        cells: Vec<Mat>,
+        preprocess: bool,
+    ) -> Result<Vec<CellReading>, ScanError> {
+        match &mut self.engines {
+            CellEngines::Local(ocr) => cells
+                .iter()
+                .map(|cell| ocr::read_cell(ocr.as_mut(), cell, preprocess))
+                .collect(),
+            CellEngines::Pool(pool) => pool.recognize_cells(cells, preprocess),
+        }
+    }
+}
+
+/// Detection thresholds, from the `opencv` settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Thresholds {
+    /// Minimum similarity of an empty buffer slot with the slot template
+    pub buffer_match: f64,
+    /// Fraction of the contrast between the background and the codes of the grid
+    pub grid: f64,
+    /// Fraction of the contrast between the background and the codes of the daemons
+    pub daemon: f64,
+}
+
+impl Thresholds {
+    pub(crate) fn from_settings() -> Thresholds {
+        let settings = settings();
+        Thresholds {
+            buffer_match: settings.opencv.buffer_match_threshold,
+            grid: settings.opencv.detect_grid_threshold,
+            daemon: settings.opencv.detect_daemon_threshold,
+        }
+    }
+}
+
+/// Scans puzzles from screenshots. The OCR engines, templates and settings are loaded once and
+/// reused by every scan.
+pub(crate) struct Scanner {
+    reader: CellReader,
+    /// Engine and known names to read the daemon names with, when enabled and available
+    daemon_names: Option<(Box<dyn TextRecognizer>, DaemonNames)>,
+    /// Empty buffer slot at the reference resolution
+    buffer_template: Mat,
+    checks: ScanChecks,
+    thresholds: Thresholds,
+    min_confidence: u8,
+    /// Features of the settings which are not available
+    startup_warnings: Vec<String>,
+}
+
+impl Scanner {
+    pub(crate) fn new() -> Result<Scanner, ScanError> {
+        let reader = CellReader::from_settings()?;
+        let mut daemon_names = None;
+        let mut startup_warnings = Vec::new();
+        if settings().opencv.read_daemon_names {
+            match ocr::text_recognizer()? {
+                Some(text_ocr) => daemon_names = Some((text_ocr, DaemonNames::from_settings()?)),
+                None => startup_warnings.push(
+                    "Daemon names are not read, since tesseract is not available".to_string(),
+                ),
+            }
+        }
+        Ok(Scanner {
+            reader,
+            daemon_names,
+            buffer_template: imread_asset(BUFFER_TEMPLATE, ImreadModes::IMREAD_GRAYSCALE)?,
+            checks: ScanChecks::from_settings(),
+            thresholds: Thresholds::from_settings(),
+            min_confidence: settings().opencv.ocr_min_confidence,
+            startup_warnings,
+        })
+    }
+
+    /// Settings which the scanner cannot honour, to report once before scanning
+    pub(crate) fn startup_warnings(&self) -> &[String] {
+        &self.startup_warnings
+    }
+
+    pub(crate) fn scan(&mut self, screen: &Mat) -> Result<ScanResult, ScanError> {
+        self.scan_debug(screen, &mut DebugDump::default())
+    }
+
+    /// Scan the screen, writing the intermediate images of each stage to the debug dump
+    pub(crate) fn scan_debug(
+        &mut self,
+        screen: &Mat,
+        debug: &mut DebugDump,
+    ) -> Result<ScanResult, ScanError> {
+        let result = self.scan_stages(screen, debug);
+        debug.finish(&result);
+        result
+    }
+
+    fn scan_stages(
+        &mut self,
+        screen: &Mat,
+        debug: &mut DebugDump,
+    ) -> Result<ScanResult, ScanError> {
+        // convert to greyscale
+        let mut grey = Mat::default();
+        imgproc::cvt_color(&screen, &mut grey, imgproc::COLOR_BGR2GRAY, 0)?;
+        // Map settings from the reference resolution to the screen resolution
+        let frame_scale = FrameScale::new(grey.size()?);
+        let layout = locate_panel(&grey, frame_scale, debug);
+        self.scan_panel(screen, &grey, &layout, debug)
+    }
+
+    /// Scan the regions of the panel layout in the screen and its greyscale image
+    fn scan_panel(
+        &mut self,
+        screen: &Mat,
+        grey: &Mat,
+        layout: &PanelLayout,
+        debug: &mut DebugDump,
+    ) -> Result<ScanResult, ScanError> {
+        let reader = &mut self.reader;
+        let scale = &layout.scale;
+        let frame = grey.size()?;
+        debug.summary.frame_width = frame.width;
+        debug.summary.frame_height = frame.height;
+        debug.summary.scale = scale.scale();
+        debug.summary.buffer_rect = Some(layout.buffer.into());
+        debug.summary.grid_rect = Some(layout.grid.into());
+        debug.summary.daemon_panel = Some(layout.daemons.into());
+        let layout_rects = [
+            layout.buffer,
+            layout.grid,
+            layout.daemons,
+            layout.daemon_names,
+        ];
+        debug.rects("layout", &screen, &layout_rects);
+
+        // Detect buffer size and the codes already in the buffer, with other thresholds while the
+        // size is out of the game range
+        let (buffer, buffer_size) = checks::retry_thresholds(
+            "buffer",
+            debug,
+            |factor, debug| {
+                let threshold = self.thresholds.buffer_match * factor;
+                scan_buffer(
+                    reader,
+                    grey,
+                    &layout.buffer,
+                    &self.buffer_template,
+                    threshold,
+                    scale,
+                    debug,
+                )
+            },
+            |&(_, size)| self.checks.check_buffer_size(size, &layout.buffer),
+        )?;
+        debug.summary.buffer_size = Some(buffer_size);
+        debug.summary.buffer_codes = buffer.iter().map(Code::to_string).collect();
+        println!("Buffer size detected: {}", buffer_size);
+
+        // Detect grid info, with other thresholds while the grid size is unexpected
+        let grid_info = checks::retry_thresholds(
+            "code matrix",
+            debug,
+            |factor, debug| {
+                let fraction = self.thresholds.grid * factor;
+                detect_grid_with(grey, &layout.grid, fraction, scale, debug)
+            },
+            |info| {
+                self.checks
+                    .check_grid_size(info.rows, info.cols, &layout.grid)
+            },
+        )?;
+        debug.summary.grid_rows = Some(grid_info.rows);
+        debug.summary.grid_cols = Some(grid_info.cols);
+        println!("Grid size detected: {}x{}", grid_info.rows, grid_info.cols);
+
+        // Process cell data
+        let (grid_data, grid_confidence) = process_grid(reader, grey, &grid_info, scale, debug)?;
+        let grid = PuzzleGrid::try_from_cells(grid_info.rows, grid_info.cols, grid_data)?;
+        let grid_confidence =
+            PuzzleGrid::try_from_cells(grid_info.rows, grid_info.cols, grid_confidence)?;
+        println!("Grid:\n{}", grid.to_string());
+
+        // Detect the progress of a partially played puzzle
+        let used_cells: Vec<CellCoord> = grid_info
+            .used
+            .iter()
+            .enumerate()
+            .filter(|(_, &used)| used)
+            .map(|(i, _)| (i as u32 / grid_info.cols, i as u32 % grid_info.cols))
+            .collect();
+        let active = detect_active_line(grey, &grid_info)?;
+        debug.summary.used_cells = used_cells.clone();
+        debug.summary.active_line = active.map(|line| format!("{:?}", line));
+        let state = PuzzleState {
+            buffer,
+            used_cells,
+            // The first row is active when nothing is highlighted
+            active: active.unwrap_or(PuzzleMove::Row(0)),
+        };
+
+        // Detect and process daemons
+        let (daemons, daemon_confidence, daemon_rows) = scan_daemons_with(
+            reader,
+            grey,
+            &layout.daemons,
+            &layout.daemon_rows,
+            self.thresholds.daemon,
+            Some(&grid),
+            scale,
+            debug,
+        )?;
+
+        let mut puzzle = Puzzle::try_with_buffer(buffer_size, grid, daemons, &state.buffer)?;
+        if let Some((text_ocr, names)) = &mut self.daemon_names {
+            puzzle.daemon_types = read_daemon_types(
+                text_ocr.as_mut(),
+                names,
+                grey,
+                &daemon_rows,
+                &layout.daemon_names,
+                scale,
+                debug,
+            )?;
+        }
+        Ok(ScanResult {
+            puzzle,
+            grid_confidence,
+            daemon_confidence,
+            min_confidence: self.min_confidence,
+            state,
+            panel: union_rect([layout.buffer, layout.grid, layout.daemons].iter()),
+            grid_area: union_rect(grid_info.cells.iter()),
+        })
+    }
+}
+
+/// Find the panel regions on screen, falling back to the fixed regions from the settings
+fn locate_panel(grey: &Mat, frame_scale: FrameScale, debug: &mut DebugDump) -> PanelLayout {
+    if settings().layout.auto {
+        if let Some(layout) = PanelLayout::locate(grey, &frame_scale) {
+            return layout;
+        }
+        debug.summary.layout_from_settings = true;
+    }
+    PanelLayout::from_settings(frame_scale)
+}
+
+/// Read an asset image, failing if the file is missing
+fn imread_asset(path: &str, flags: ImreadModes) -> Result<Mat, ScanError> {
+    let image = imread(path, flags as i32)?;
+    if image.empty()? {
+        return Err(ScanError::MissingAsset(path.to_string()));
+    }
+    Ok(image)
+}
+
+/// Find the empty buffer slots by matching the slot template, sorted from left to right
+fn detect_buffer_slots(
+    grey: &Mat,
+    rect: &cv::Rect,
+    buffer_template: &Mat,
+    match_threshold: f64,
+    scale: &FrameScale,
+    debug: &mut DebugDump,
+) -> Result<Vec<cv::Rect>, ScanError> {
+    // Get buffer section
+    let buffer = Mat::roi(&grey, *rect)?;
+
+    // Match buffer template on the grey image, since the normalized correlation does not depend
+    // on brightness and contrast
+    // The template is captured at the reference resolution
+    let interpolation = if scale.scale() < 1.0 {
+        imgproc::INTER_AREA
+    } else {
+        imgproc::INTER_LINEAR
+    };
+    let mut scaled_template = Mat::default();
+    imgproc::resize(
+        buffer_template,
+        &mut scaled_template,
+        cv::Size::new(0, 0),
+        scale.scale(),
+        scale.scale(),
+        interpolation,
+    )?;
+
+    // Find matches, each pixel represents the template similarity from 0 (worst) to 1 (best)
+    let mut match_result = Mat::default();
+    let mask = cv::no_array()?;
+    imgproc::match_template(
+        &buffer,
+        &scaled_template,
+        &mut match_result,
+        imgproc::TemplateMatchModes::TM_CCOEFF_NORMED as i32,
+        &mask,
+    )?;
+    debug.image("buffer_match", &match_result);
+    // Find maximum spots by threshold and count points above threshold
+    let mut thr_match_result = Mat::default();
+    imgproc::threshold(
+        &match_result,
+        &mut thr_match_result,
+        match_threshold,
+        255.0,
+        imgproc::THRESH_BINARY,
+    )?;
+
+    // Count match spots, since at higher resolutions a match may span more than one point
+    let mut match_spots = Mat::default();
+    thr_match_result.convert_to(&mut match_spots, cv::CV_8U, 1.0, 0.0)?;
+    let template_size = scaled_template.size()?;
+    let mut slots: Vec<cv::Rect> = get_contour_rects(&match_spots, 0)?
+        .into_iter()
+        .map(|spot| {
+            // Each point is the top left corner of a match, take the center of the spot
+            let corner = cv::Point::new(
+                rect.x + spot.x + spot.width / 2,
+                rect.y + spot.y + spot.height / 2,
+            );
+            cv::Rect::from_point_size(corner, template_size)
+        })
+        .collect();
+    slots.sort_by_key(|slot| slot.x);
+    Ok(slots)
+}
+
+/// Check if a buffer slot shows a code, i.e. if glyph pixels, halfway from the background to the
+/// text level, cover the middle of the slot. Lines such as the panel border are too thin to count
+/// as glyphs.
+fn buffer_slot_filled(grey: &Mat, slot: &cv::Rect, levels: &Levels) -> Result<bool, ScanError> {
+    let threshold = levels.level(0.5);
+    let inner = cv::Rect::new(
+        slot.x + slot.width / 4,
+        slot.y + slot.height / 4,
+        slot.width / 2,
+        slot.height / 2,
+    );
+    let mut thr_inner = Mat::default();
+    imgproc::threshold(
+        &Mat::roi(grey, inner)?,
+        &mut thr_inner,
+        threshold,
+        255.0,
+        imgproc::THRESH_BINARY,
+    )?;
+    let mut points = Mat::default();
+    cv::find_non_zero(&thr_inner, &mut points)?;
+    if points.rows() <= inner.area() / 20 {
+        return Ok(false);
+    }
+    let bounds = imgproc::bounding_rect(&points)?;
+    Ok(bounds.width >= inner.width / 2 && bounds.height >= inner.height / 2)
+}
+
+/// Read the codes already in the buffer and detect the buffer size.
+/// Filled slots come before the empty ones found by `detect_buffer_slots`, one slot width apart.
+fn scan_buffer(
+    reader: &mut CellReader,
+    grey: &Mat,
+    rect: &cv::Rect,
+    buffer_template: &Mat,
+    match_threshold: f64,
+    scale: &FrameScale,
+    debug: &mut DebugDump,
+) -> Result<(Vec<Code>, u32), ScanError> {
+    let empty_slots =
+        detect_buffer_slots(grey, rect, buffer_template, match_threshold, scale, debug)?;
+    let first_empty = match empty_slots.first() {
+        Some(slot) => *slot,
+        // A full buffer has no empty slot, the puzzle is over
+        None => return Ok((Vec::new(), 0)),
+    };
+
+    // Walk left from the first empty slot while slots show a code
+    let slot_width = scale.length(settings().buffer.slot_width);
+    let levels = Levels::measure(&Mat::roi(grey, *rect)?)?;
+    let mut filled_slots = Vec::new();
+    let mut slot = first_empty;
+    slot.x -= slot_width;
+    while slot.x >= rect.x && buffer_slot_filled(grey, &slot, &levels)? {
+        filled_slots.insert(0, slot);
+        slot.x -= slot_width;
+    }
+    let mut slot_rects = filled_slots.clone();
+    slot_rects.extend_from_slice(&empty_slots);
+    debug.rects("buffer_slots", grey, &slot_rects);
+
+    let buffer = filled_slots
+        .iter()
+        .enumerate()
+        .map(|(i, slot)| {
+            // Leave out the slot border
+            let glyphs = cv::Rect::new(
+                slot.x + slot.width / 8,
+                slot.y + slot.height / 8,
+                slot.width * 3 / 4,
+                slot.height * 3 / 4,
+            );
+            let slot_name = format!("buffer/{}", i + 1);
+            read_cell(reader, grey, &glyphs, scale, debug, &slot_name, &|_| true)
+                .map(|(code, _)| code)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let buffer_size = (filled_slots.len() + empty_slots.len()) as u32;
+    Ok((buffer, buffer_size))
+}
+
+struct CellScanInfo {
+    rows: u32,
+    cols: u32,
+    cells: Vec<cv::Rect>,
+    /// Cells without a code, as shown for the cells already selected
+    used: Vec<bool>,
+}
+
+fn get_contour_rects(img: &Mat, area_threshold: i32) -> Result<Vec<cv::Rect>, ScanError> {
+    // Outscribe bounding box around masked cells
+    let mut cell_contours = opencv::types::VectorOfVectorOfPoint::new();
+    let offset = cv::Point::new(0, 0);
+    imgproc::find_contours(
+        &img,
+        &mut cell_contours,
+        imgproc::RETR_LIST,
+        imgproc::CHAIN_APPROX_SIMPLE,
+        offset,
+    )?;
+
+    let mut rects = Vec::new();
+    for countour in &cell_contours {
+        let rect = imgproc::bounding_rect(&countour)?;
+        if rect.area() > area_threshold {
+            rects.push(rect);
+        }
+    }
+
+    Ok(rects)
+}
+
+fn dilate_rect(
+    grid_img: &Mat,
+    ksize: cv::Size,
+    area_threshold: i32,
+    debug: &mut DebugDump,
+    debug_name: &str,
+) -> Result<Vec<cv::Rect>, ScanError> {
+    let anchor = cv::Point::new(-1, -1);
+    let border_value = imgproc::morphology_default_border_value()?;
+    let kernel = imgproc::get_structuring_element(imgproc::MORPH_RECT, ksize, anchor)?;
+    let mut dilate = Mat::default();
+    imgproc::dilate(
+        &grid_img,
+        &mut dilate,
+        &kernel,
+        anchor,
+        1,
+        cv::BORDER_ISOLATED,
+        border_value,
+    )?;
+    debug.image(debug_name, &dilate);
+
+    let mut rects = get_contour_rects(&dilate, area_threshold)?;
+    // transform coordinates from ROI to parent coordinates
+    let mut size = cv::Size::new(0, 0);
+    let mut offset = cv::Point::new(0, 0);
+    grid_img.locate_roi(&mut size, &mut offset)?;
+    rects.iter_mut().for_each(|rect| {
+        rect.x += offset.x;
+        rect.y += offset.y;
+    });
+
+    Ok(rects)
+}
+
+/// Find the grid rows, columns and cells, with codes brighter than the background by `fraction`
+/// of the contrast
+fn detect_grid_with(
+    grey: &Mat,
+    grid_roi: &cv::Rect,
+    fraction: f64,
+    scale: &FrameScale,
+    debug: &mut DebugDump,
+) -> Result<CellScanInfo, ScanError> {
+    // Blur grid then apply threshold to find cells, relative to the levels of the grid area
+    let mut blur = Mat::default();
+    let blur_kernel = scale.odd_kernel(35, 29);
+    imgproc::gaussian_blur(&grey, &mut blur, blur_kernel, 0.0, 0.0, cv::BORDER_DEFAULT)?;
+    let levels = Levels::measure(&Mat::roi(grey, *grid_roi)?)?;
+    let thr_img = levels::threshold_blobs(&blur, &levels, fraction, scale)?;
+
+    // Extract ROI grid max limit area
+    let cell_min_area = scale.area(25 * 25);
+    let min_size = cv::Size::new(5, 5);
+
+    let mut roi = *grid_roi;
+    let mut grid_thr_img = Mat::roi(&thr_img, roi)?;
+    debug.image("grid_threshold", &grid_thr_img);
+
+    // Dilate horizontally to detect rows
+    let dilate_row = scale.length(50);
+    let kernel_h = cv::Size::new(dilate_row, 1);
+    let row_area_threshold = cell_min_area * min_size.width;
+    let mut rows = dilate_rect(
+        &grid_thr_img,
+        kernel_h,
+        row_area_threshold,
+        debug,
+        "grid_rows",
+    )?;
+    if rows.is_empty() {
+        return Err(ScanError::GridNotFound { rect: *grid_roi });
+    }
+    // sort rows by y coordinate
+    rows.sort_by_key(|row| row.y);
+
+    // Adjust top and bottom grid rect if smaller. This avoids extranous data noise during column detection
+    roi.y = rows.first().map(|row| row.y).unwrap_or(roi.y);
+    let new_grid_bottom_y = rows
+        .last()
+        .map(|row| row.y + row.height)
+        .unwrap_or(roi.y + roi.height);
+    roi.height = new_grid_bottom_y - roi.y;
+    grid_thr_img = Mat::roi(&thr_img, roi)?;
+
+    // Dilate vertically to detect cols
+    let dilate_col = scale.length(50);
+    let kernel_v = cv::Size::new(1, dilate_col);
+    let col_area_threshold = cell_min_area * min_size.height;
+    let mut cols = dilate_rect(
+        &grid_thr_img,
+        kernel_v,
+        col_area_threshold,
+        debug,
+        "grid_cols",
+    )?;
+    if cols.is_empty() {
+        return Err(ScanError::GridNotFound { rect: *grid_roi });
+    }
+    // Sort cols by x thr_img coordinate
+    cols.sort_by_key(|col| col.x);
+
+    // Map rows and cols to cells rectangles
+    let mut cells = Vec::new();
+    for row in &rows {
+        for col in &cols {
+            // map to image coordinates
+            let cell_rect = cv::Rect::new(col.x, row.y, col.width, row.height);
+            cells.push(cell_rect);
+        }
+    }
+    debug.rects("grid_cells", grey, &cells);
+    // Used cells are blank, so their thresholded area is mostly empty
+    let used = cells
+        .iter()
+        .map(|cell| Ok(cv::count_non_zero(&Mat::roi(&thr_img, *cell)?)? < cell.area() / 4))
+        .collect::<Result<Vec<bool>, ScanError>>()?;
+    let grid_info = CellScanInfo {
+        rows: rows.len() as u32,
+        cols: cols.len() as u32,
+        cells,
+        used,
+    };
+    Ok(grid_info)
+}
+
+/// Blurred and thresholded frame where each daemon code is a solid blob, brighter than the
+/// background by `fraction` of the contrast of the daemon panel
+fn daemon_threshold(
+    grey: &Mat,
+    panel: &cv::Rect,
+    fraction: f64,
+    scale: &FrameScale,
+) -> Result<Mat, ScanError> {
+    let mut blur = Mat::default();
+    let blur_kernel = scale.odd_kernel(35, 29);
+    imgproc::gaussian_blur(&grey, &mut blur, blur_kernel, 0.0, 0.0, cv::BORDER_DEFAULT)?;
+    let levels = Levels::measure(&Mat::roi(grey, *panel)?)?;
+    levels::threshold_blobs(&blur, &levels, fraction, scale)
+}
+
+/// Find the sequence rows in the daemon panel, as rectangles of the configured row height.
+/// Rows not starting at the left of the panel or taller than a code, such as the daemon icons,
+/// are dropped.
+fn detect_daemon_rows(
+    thr_img: &Mat,
+    panel: &cv::Rect,
+    scale: &FrameScale,
+    debug: &mut DebugDump,
+) -> Result<Vec<cv::Rect>, ScanError> {
+    let panel_thr_img = Mat::roi(&thr_img, *panel)?;
+    debug.image("daemon_threshold", &panel_thr_img);
+
+    // Dilate horizontally to detect rows
+    let dilate_row = scale.length(50);
+    let kernel_h = cv::Size::new(dilate_row, 1);
+    let row_area_threshold = scale.area(25 * 25) * settings().daemons.min_length as i32;
+    let mut rows = dilate_rect(
+        &panel_thr_img,
+        kernel_h,
+        row_area_threshold,
+        debug,
+        "daemon_rows",
+    )?;
+
+    let max_offset = scale.length(settings().daemons.cell_width);
+    let row_height = scale.length(settings().daemons.row_height);
+    rows.retain(|row| row.x - panel.x <= max_offset && row.height <= row_height);
+    // sort rows by y coordinate
+    rows.sort_by_key(|row| row.y);
+
+    // Center the rows on the codes, keeping the margin expected by the OCR
+    let frame = thr_img.size()?;
+    let rows = rows
+        .iter()
+        .map(|row| {
+            let top = row.y + row.height / 2 - row_height / 2;
+            clamp_rect(cv::Rect::new(row.x, top, row.width, row_height), frame)
+        })
+        .collect();
+    Ok(rows)
+}
+
+fn detect_daemon_size(
+    thr_img: &Mat,
+    roi: &cv::Rect,
+    scale: &FrameScale,
+    debug: &mut DebugDump,
+    debug_name: &str,
+) -> Result<Option<CellScanInfo>, ScanError> {
+    // Get daemon region of interest
+    let grid_thr_img = Mat::roi(&thr_img, *roi)?;
+    debug.image(&format!("{}_threshold", debug_name), &grid_thr_img);
+
+    // Dilate vertically to detect cols
+    let dilate_col = scale.length(50);
+    let kernel_v = cv::Size::new(1, dilate_col);
+    let cell_min_area = scale.area(20 * 20);
+    let cols_name = format!("{}_cols", debug_name);
+    let mut cols = dilate_rect(&grid_thr_img, kernel_v, cell_min_area, debug, &cols_name)?;
+    // Drop blobs wider than a code, such as lines of text below the panel
+    let max_width = scale.length(settings().daemons.cell_width) * 3 / 2;
+    cols.retain(|col| col.width <= max_width);
+    // Skip this daemon ROI if cols are not of the min length
+    if cols.len() < settings().daemons.min_length {
+        return Ok(None);
+    };
+    // Sort cols by x thr_img coordinate
+    cols.sort_by_key(|col| col.x);
+
+    // Map rows and cols to cells rectangles
+    let cells: Vec<cv::Rect> = cols
+        .iter()
+        .map(|col| cv::Rect::new(col.x, roi.y, col.width, roi.height))
+        .collect();
+    let used = vec![false; cells.len()];
+    let grid_info = CellScanInfo {
+        rows: 1,
+        cols: cols.len() as u32,
+        cells,
+        used,
+    };
+    Ok(Some(grid_info))
+}
+
+/// Segment the daemon panel into sequence rows and their cells, with codes brighter than the
+/// background by `fraction` of the contrast.
+/// The rows from the settings are only used when no row is detected in the panel.
+fn detect_daemons_with(
+    grey: &Mat,
+    panel: &cv::Rect,
+    hint_rows: &[cv::Rect],
+    fraction: f64,
+    scale: &FrameScale,
+    debug: &mut DebugDump,
+) -> Result<Vec<(cv::Rect, CellScanInfo)>, ScanError> {
+    let thr_img = daemon_threshold(grey, panel, fraction, scale)?;
+    let mut rows = detect_daemon_rows(&thr_img, panel, scale, debug)?;
+    debug.summary.daemon_rows_from_settings = rows.is_empty();
+    if rows.is_empty() {
+        rows = hint_rows.to_vec();
+    }
+
+    let mut daemons = Vec::new();
+    for row in rows {
+        let debug_name = format!("daemon_{}", daemons.len() + 1);
+        if let Some(cell_info) = detect_daemon_size(&thr_img, &row, scale, debug, &debug_name)? {
+            daemons.push((row, cell_info));
+        }
+    }
+    debug.summary.daemon_rows = daemons.iter().map(|&(row, _)| row.into()).collect();
+    let cells: Vec<cv::Rect> = daemons
+        .iter()
+        .flat_map(|(_, cell_info)| cell_info.cells.iter().copied())
+        .collect();
+    debug.rects("daemon_cells", grey, &cells);
+    Ok(daemons)
+}
+
+/// Read the daemon sequences, detected with `fraction` of the contrast. When the grid is given,
+/// codes which are not in the grid are read again with other preprocessing.
+#[allow(clippy::too_many_arguments)]
+fn scan_daemons_with(
+    reader: &mut CellReader,
+    img: &Mat,
+    panel: &cv::Rect,
+    hint_rows: &[cv::Rect],
+    fraction: f64,
+    grid: Option<&PuzzleGrid>,
+    scale: &FrameScale,
+    debug: &mut DebugDump,
+) -> Result<(Vec<PuzzleDaemon>, Vec<Vec<u8>>, Vec<cv::Rect>), ScanError> {
+    let mut daemons = Vec::<PuzzleDaemon>::new();
+    let mut confidence = Vec::new();
+    let detected = detect_daemons_with(img, panel, hint_rows, fraction, scale, debug)?;
+    let rows = detected.iter().map(|&(row, _)| row).collect();
+    for (n, (daemon_roi, cell_info)) in detected.iter().enumerate() {
+        println!("Daemon size detected: {}", cell_info.cols);
+        // Extract sequence cells, the cells of a row in one batch
+        let named_cells: Vec<(cv::Rect, String)> = cell_info
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| (*cell, format!("daemons/{}_{}", n + 1, i + 1)))
+            .collect();
+        let in_grid = |code: &Code| grid.map_or(true, |grid| checks::grid_may_contain(grid, code));
+        match read_cells(reader, img, &named_cells, scale, debug, &in_grid) {
+            Ok(codes) => {
+                let (daemon, daemon_confidence) = codes.into_iter().unzip();
+                daemons.push(daemon);
+                confidence.push(daemon_confidence);
+            }
+            Err(ScanError::InvalidCode { text, .. }) => {
+                return Err(ScanError::DaemonRowUnreadable {
+                    rect: *daemon_roi,
+                    text,
+                })
+            }
+            Err(error) => return Err(error),
+        }
+    }
+    Ok((daemons, confidence, rows))
+}
+
+/// Read the name on the right of each daemon row and map it to a known daemon type
+fn read_daemon_types(
+    ocr: &mut dyn TextRecognizer,
+    names: &DaemonNames,
+    img: &Mat,
+    rows: &[cv::Rect],
+    names_area: &cv::Rect,
+    scale: &FrameScale,
+    debug: &mut DebugDump,
+) -> Result<Vec<DaemonType>, ScanError> {
+    let frame = img.size()?;
+    let mut daemon_types = Vec::new();
+    for (n, row) in rows.iter().enumerate() {
+        let name_rect = cv::Rect::new(names_area.x, row.y, names_area.width, row.height);
+        let name_rect = clamp_rect(name_rect, frame);
+        if name_rect.area() == 0 {
+            daemon_types.push(DaemonType::Unknown);
+            continue;
+        }
+        let roi = reference_roi(img, &name_rect, scale)?;
+        debug.image(&format!("daemons/{}_name", n + 1), &roi);
+        let text = ocr.recognize_text(&roi)?.text;
+        let daemon_type = names.classify(&text);
+        debug.summary.daemon_names.push(text);
+        debug
+            .summary
+            .daemon_types
+            .push(daemon_type.id().to_string());
+        daemon_types.push(daemon_type);
+    }
+    Ok(daemon_types)
+}
+
+fn process_grid(
+    reader: &mut CellReader,
+    grey: &Mat,
+    grid_info: &CellScanInfo,
+    scale: &FrameScale,
+    debug: &mut DebugDump,
+) -> Result<(Vec<Cell>, Vec<u8>), ScanError> {
+    // There is nothing to read in a used cell, the others are read in one batch
+    let named_cells: Vec<(cv::Rect, String)> = grid_info
+        .cells
+        .iter()
+        .zip(grid_info.used.iter())
+        .enumerate()
+        .filter(|(_, (_, &used))| !used)
+        .map(|(i, (cell, _))| {
+            let (row, col) = (i as u32 / grid_info.cols, i as u32 % grid_info.cols);
+            (*cell, format!("cells/r{}c{}", row + 1, col + 1))
+        })
+        .collect();
+    let mut codes = read_cells(reader, grey, &named_cells, scale, debug, &|_| true)?.into_iter();
+    // Back to row-major order with the used cells
+    let cells: Vec<(Cell, u8)> = grid_info
+        .used
+        .iter()
+        .map(|&used| {
+            if used {
+                (Cell::Used, 100)
+            } else {
+                let (code, confidence) = codes
+                    .next()
+                    .expect("a code for each cell which is not used");
+                (Cell::Code(code), confidence)
+            }
+        })
+        .collect();
+    Ok(cells.into_iter().unzip())
+}
+
+/// Smallest rectangle containing all the given rectangles
+fn union_rect<'a>(rects: impl Iterator<Item = &'a cv::Rect>) -> cv::Rect {
+    let (mut left, mut top, mut right, mut bottom) = (i32::MAX, i32::MAX, i32::MIN, i32::MIN);
+    for rect in rects {
+        left = left.min(rect.x);
+        top = top.min(rect.y);
+        right = right.max(rect.x + rect.width);
+        bottom = bottom.max(rect.y + rect.height);
+    }
+    if left > right {
+        return cv::Rect::default();
+    }
+    cv::Rect::new(left, top, right - left, bottom - top)
+}
+
+/// Median grey level of an image region, i.e. its background when text covers less than half
+fn median_grey(grey: &Mat, rect: &cv::Rect) -> Result<u8, ScanError> {
+    levels::median(&Mat::roi(grey, *rect)?)
+}
+
+/// Find the highlighted row or column of the grid, where the next code is selected.
+/// Its background is brighter than the other lines by at least `opencv.detect_active_line_delta`,
+/// as a fraction of the contrast between the grid background and the codes.
+fn detect_active_line(
+    grey: &Mat,
+    grid_info: &CellScanInfo,
+) -> Result<Option<PuzzleMove>, ScanError> {
+    let grid_levels = Levels::measure(&Mat::roi(grey, union_rect(grid_info.cells.iter()))?)?;
+    let delta = settings().opencv.detect_active_line_delta * grid_levels.contrast();
+    let cols = grid_info.cols as usize;
+    let row_levels = grid_info
+        .cells
+        .chunks(cols)
+        .map(|row_cells| median_grey(grey, &union_rect(row_cells.iter())))
+        .collect::<Result<Vec<u8>, _>>()?;
+    let col_levels = (0..cols)
+        .map(|col| {
+            median_grey(
+                grey,
+                &union_rect(grid_info.cells.iter().skip(col).step_by(cols)),
+            )
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    // Most lines are not highlighted, so the median level is the plain background
+    let mut line_levels: Vec<u8> = row_levels
+        .iter()
+        .chain(col_levels.iter())
+        .copied()
+        .collect();
+    line_levels.sort_unstable();
+    let background = line_levels[line_levels.len() / 2] as f64;
+
+    let lines = row_levels
+        .iter()
+        .enumerate()
+        .map(|(row, &level)| (PuzzleMove::Row(row as u32), level))
+        .chain(
+            col_levels
+                .iter()
+                .enumerate()
+                .map(|(col, &level)| (PuzzleMove::Column(col as u32), level)),
+        );
+    let active = lines
+        .max_by_key(|&(_, level)| level)
+        .filter(|&(_, level)| level as f64 - background > delta)
+        .map(|(line, _)| line);
+    Ok(active)
+}
+
+/// Crop an image region, scaled to the reference resolution since OCR settings are tuned for
+/// glyphs at that size
+fn reference_roi(img: &Mat, rect: &cv::Rect, scale: &FrameScale) -> Result<Mat, ScanError> {
+    let mut roi = Mat::roi(img, *rect)?;
+    if scale.scale() != 1.0 {
+        let mut scaled_roi = Mat::default();
+        let factor = 1.0 / scale.scale();
+        imgproc::resize(
+            &roi,
+            &mut scaled_roi,
+            cv::Size::new(0, 0),
+            factor,
+            factor,
+            imgproc::INTER_AREA,
+        )?;
+        roi = scaled_roi;
+    }
+    Ok(roi)
+}
+
+/// Recognize the code in a cell, reading it again with the alternate preprocessing of
+/// `RETRY_PREPROCESS` while it is not a valid code or not accepted. The first reading is kept when
+/// no other is accepted.
+fn read_cell(
+    reader: &mut CellReader,
+    img: &Mat,
+    cell: &cv::Rect,
+    scale: &FrameScale,
+    debug: &mut DebugDump,
+    debug_name: &str,
+    accept: &dyn Fn(&Code) -> bool,
+) -> Result<(Code, u8), ScanError> {
+    let cells = [(*cell, debug_name.to_string())];
+    Ok(read_cells(reader, img, &cells, scale, debug, accept)?.remove(0))
+}
+
+/// Recognize the codes of named cells like `read_cell`, with the first reading of all the cells in
+/// one batch, which is parallel when the reader has a pool of engines. The codes are in the order
+/// of the cells.
+fn read_cells(
+    reader: &mut CellReader,
+    img: &Mat,
+    cells: &[(cv::Rect, String)],
+    scale: &FrameScale,
+    debug: &mut DebugDump,
+    accept: &dyn Fn(&Code) -> bool,
+) -> Result<Vec<(Code, u8)>, ScanError> {
+    let mut rois = Vec::with_capacity(cells.len());
+    for (cell, debug_name) in cells {
+        let roi = preprocess_cell(img, cell, CellPreprocess::Plain, scale)?;
+        debug.image(debug_name, &roi);
+        rois.push(roi);
+    }
+    let readings = reader.recognize_all(rois, debug.enabled())?;
+
+    let mut codes = Vec::with_capacity(cells.len());
+    'cells: for ((cell, debug_name), reading) in cells.iter().zip(readings) {
+        let first = interpret_cell(reader, cell, reading, debug, debug_name);
+        let reason = match &first {
+            Ok((code, _)) if accept(code) => None,
+            Ok((code, _)) => Some(format!("code {} is not in the code matrix", code)),
+            Err(error @ ScanError::InvalidCode { .. }) => Some(error.to_string()),
+            Err(_) => None,
+        };
+        let reason = match reason {
+            Some(reason) => reason,
+            None => {
+                codes.push(first?);
+                continue;
+            }
+        };
+        debug
+            .summary
+            .retries
+            .push(format!("{}: {}", debug_name, reason));
+        for (n, &preprocess) in RETRY_PREPROCESS.iter().enumerate() {
+            let retry_name = format!("{}_retry{}", debug_name, n + 1);
+            match extract_cell_with(reader, img, cell, preprocess, scale, debug, &retry_name) {
+                Ok((code, confidence)) if accept(&code) => {
+                    debug.summary.retries.push(format!(
+                        "{}: read again as {} with {:?}",
+                        debug_name, code, preprocess
+                    ));
+                    codes.push((code, confidence));
+                    continue 'cells;
+                }
+                Ok(_) | Err(ScanError::InvalidCode { .. }) => (),
+                Err(error) => return Err(error),
+            }
+        }
+        codes.push(first?);
+    }
+    Ok(codes)
+}
+
+/// Cell image as given to the OCR engine, at the reference resolution
+fn preprocess_cell(
+    img: &Mat,
+    cell: &cv::Rect,
+    preprocess: CellPreprocess,
+    scale: &FrameScale,
+) -> Result<Mat, ScanError> {
+    let margin = match preprocess {
+        CellPreprocess::Grow(margin) => scale.length(margin),
+        CellPreprocess::Shrink(margin) => -scale
+            .length(margin)
+            .min((cell.width.min(cell.height) - 1) / 2),
+        CellPreprocess::Plain | CellPreprocess::Blur => 0,
+    };
+    let rect = cv::Rect::new(
+        cell.x - margin,
+        cell.y - margin,
+        cell.width + 2 * margin,
+        cell.height + 2 * margin,
+    );
+    let roi = reference_roi(img, &clamp_rect(rect, img.size()?), scale)?;
+    if preprocess != CellPreprocess::Blur {
+        return Ok(roi);
+    }
+    let mut blur = Mat::default();
+    let kernel = cv::Size::new(3, 3);
+    imgproc::gaussian_blur(&roi, &mut blur, kernel, 0.0, 0.0, cv::BORDER_DEFAULT)?;
+    Ok(blur)
+}
+
+/// Recognize the code in a cell preprocessed as given, along with the recognition confidence
+fn extract_cell_with(
+    reader: &mut CellReader,
+    img: &Mat,
+    cell: &cv::Rect,
+    preprocess: CellPreprocess,
+    scale: &FrameScale,
+    debug: &mut DebugDump,
+    debug_name: &str,
+) -> Result<(Code, u8), ScanError> {
+    let roi = preprocess_cell(img, cell, preprocess, scale)?;
+    debug.image(debug_name, &roi);
+    let reading = reader.recognize(&roi, debug.enabled())?;
+    interpret_cell(reader, cell, reading, debug, debug_name)
+}
+
+/// Code of a cell from the text read by the OCR engine, along with the recognition confidence
+fn interpret_cell(
+    reader: &CellReader,
+    cell: &cv::Rect,
+    (reading, preprocessed): CellReading,
+    debug: &mut DebugDump,
+    debug_name: &str,
+) -> Result<(Code, u8), ScanError> {
+    if let Some(preprocessed) = preprocessed {
+        debug.image(&format!("{}_ocr", debug_name), &preprocessed);
+    }
+    let OcrText {
+        text: ocr_text,
+        confidence,
+    } = reading;
+    let text = OCR_CORRECTIONS
+        .iter()
+        .find(|(mistake, _)| *mistake == ocr_text)
+        .map_or(ocr_text.clone(), |(_, text)| (*text).to_owned());
+
+    // Check for invalid code
+    let code = text
+        .parse::<Code>()
+        .and_then(|code| code.validate(&reader.valid_codes));
+    debug.summary.cells.push(CellSummary {
+        name: debug_name.to_string(),
+        rect: (*cell).into(),
+        text: ocr_text,
+        code: code.as_ref().ok().map(Code::to_string),
+        confidence,
+    });
+    let code = code.map_err(|_| ScanError::InvalidCode { cell: *cell, text })?;
+    Ok((code, confidence))
+}
+
+// TESTS
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::configuration::tests::load_default_settings;
+    use crate::glyphs::GlyphClassifier;
+    use crate::ocr::ScriptedRecognizer;
+
+    static FILE_TEST_5: &str = "test/test_5x5.jpg";
+    static FILE_TEST_6: &str = "test/test_6x6.png";
+    static FILE_TEST_6_2: &str = "test/test_6x6_2.jpg";
+    static FILE_TEST_4_DAEMONS: &str = "test/test_4-daemons.jpg";
+
+    /// Expected grid codes of the test screenshots
+    #[rustfmt::skip]
+    static CELLS_TEST_5: [&str; 25] = [
+        "55","55","1C","55","55",
+        "55","E9","BD","1C","BD",
+        "E9","1C","1C","1C","55",
+        "E9","1C","BD","1C","BD",
+        "55","55","BD","55","BD",
+    ];
+    #[rustfmt::skip]
+    static CELLS_TEST_6: [&str; 36] = [
+        "E9","1C","55","55","55","1C",
+        "55","55","55","7A","BD","BD",
+        "BD","E9","E9","55","BD","1C",
+        "1C","1C","7A","55","55","7A",
+        "7A","7A","55","55","1C","55",
+        "E9","E9","1C","BD","55","7A",
+    ];
+    #[rustfmt::skip]
+    static CELLS_TEST_6_2: [&str; 36] = [
+        "E9","55","E9","55","E9","55",
+        "BD","1C","7A","7A","55","E9",
+        "1C","55","7A","1C","1C","E9",
+        "7A","1C","7A","BD","BD","55",
+        "7A","55","1C","E9","55","7A",
+        "BD","1C","1C","55","55","7A",
+    ];
+
+    /// Resolutions to rescale the 1080p test screenshots to
+    static TEST_RESOLUTIONS: [(i32, i32); 3] = [(2560, 1440), (3840, 2160), (1366, 768)];
+
+    /// Layout from the settings, scaled to the test image
+    fn settings_layout(img: &Mat) -> PanelLayout {
+        PanelLayout::from_settings(FrameScale::new(img.size().unwrap()))
+    }
+
+    /// Scan with a scanner of its own
+    fn scan(screen: &Mat) -> Result<ScanResult, ScanError> {
+        Scanner::new()?.scan(screen)
+    }
+
+    fn cell_reader(ocr: impl CellRecognizer + 'static) -> CellReader {
+        CellReader::new(Box::new(ocr)).unwrap()
+    }
+
+    /// Number of empty buffer slots, with the template and threshold of a scanner
+    fn detect_buffer_size(
+        grey: &Mat,
+        rect: &cv::Rect,
+        scale: &FrameScale,
+        debug: &mut DebugDump,
+    ) -> Result<u32, ScanError> {
+        let template = imread_asset(BUFFER_TEMPLATE, ImreadModes::IMREAD_GRAYSCALE)?;
+        let match_threshold = Thresholds::from_settings().buffer_match;
+        Ok(detect_buffer_slots(grey, rect, &template, match_threshold, scale, debug)?.len() as u32)
+    }
+
+    /// Grid cells, with the threshold of a scanner
+    fn detect_grid(
+        grey: &Mat,
+        grid_roi: &cv::Rect,
+        scale: &FrameScale,
+        debug: &mut DebugDump,
+    ) -> Result<CellScanInfo, ScanError> {
+        let fraction = Thresholds::from_settings().grid;
+        detect_grid_with(grey, grid_roi, fraction, scale, debug)
+    }
+
+    /// Daemon sequence rows and cells, with the threshold of a scanner
+    fn detect_daemons(
+        grey: &Mat,
+        panel: &cv::Rect,
+        hint_rows: &[cv::Rect],
+        scale: &FrameScale,
+        debug: &mut DebugDump,
+    ) -> Result<Vec<(cv::Rect, CellScanInfo)>, ScanError> {
+        let fraction = Thresholds::from_settings().daemon;
+        detect_daemons_with(grey, panel, hint_rows, fraction, scale, debug)
+    }
+
+    /// Daemon sequences, with the threshold of a scanner
+    fn scan_daemons(
+        reader: &mut CellReader,
+        img: &Mat,
+        panel: &cv::Rect,
+        hint_rows: &[cv::Rect],
+        grid: Option<&PuzzleGrid>,
+        scale: &FrameScale,
+        debug: &mut DebugDump,
+    ) -> Result<(Vec<PuzzleDaemon>, Vec<Vec<u8>>, Vec<cv::Rect>), ScanError> {
+        let fraction = Thresholds::from_settings().daemon;
+        scan_daemons_with(reader, img, panel, hint_rows, fraction, grid, scale, debug)
+    }
+
+    /// Code of a cell read without retry preprocessing, along with the recognition confidence
+    fn extract_cell(
+        reader: &mut CellReader,
+        img: &Mat,
+        cell: &cv::Rect,
+        scale: &FrameScale,
+        debug: &mut DebugDump,
+        debug_name: &str,
+    ) -> Result<(Code, u8), ScanError> {
+        let preprocess = CellPreprocess::Plain;
+        extract_cell_with(reader, img, cell, preprocess, scale, debug, debug_name)
+    }
+
+    fn imread_scaled(path: &str, flags: ImreadModes, (width, height): (i32, i32)) -> Mat {
+        let test_screen =
+            imread(path, flags as i32).expect(format!("File {} not found", path).as_str());
+        let mut scaled_screen = Mat::default();
+        imgproc::resize(
+            &test_screen,
+            &mut scaled_screen,
+            cv::Size::new(width, height),
+            0.0,
+            0.0,
+            imgproc::INTER_AREA,
+        )
+        .unwrap();
+        scaled_screen
+    }
+
+    #[test]
+    fn test_buffer_detect_7() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_4_DAEMONS, ImreadModes::IMREAD_GRAYSCALE as i32)
+            .expect(format!("File {} not found", FILE_TEST_4_DAEMONS).as_str());
+        let layout = settings_layout(&test_screen);
+        let buffer_size = detect_buffer_size(
+            &test_screen,
+            &layout.buffer,
+            &layout.scale,
+            &mut DebugDump::default(),
+        )
+        .unwrap();
+        assert_eq!(buffer_size, 7);
+    }
+
+    #[test]
+    fn test_buffer_detect_8() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_6, ImreadModes::IMREAD_GRAYSCALE as i32)
+            .expect(format!("File {} not found", FILE_TEST_6).as_str());
+        let layout = settings_layout(&test_screen);
+        let buffer_size = detect_buffer_size(
+            &test_screen,
+            &layout.buffer,
+            &layout.scale,
+            &mut DebugDump::default(),
+        )
+        .unwrap();
+        assert_eq!(buffer_size, 8);
+    }
+
+    #[test]
+    fn test_buffer_detect_8_2() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_6_2, ImreadModes::IMREAD_GRAYSCALE as i32)
+            .expect(format!("File {} not found", FILE_TEST_6_2).as_str());
+        let layout = settings_layout(&test_screen);
+        let buffer_size = detect_buffer_size(
+            &test_screen,
+            &layout.buffer,
+            &layout.scale,
+            &mut DebugDump::default(),
+        )
+        .unwrap();
+        assert_eq!(buffer_size, 8);
+    }
+
+    #[test]
+    fn test_grid_detect_5() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_5, ImreadModes::IMREAD_GRAYSCALE as i32)
+            .expect(format!("File {} not found", FILE_TEST_5).as_str());
+        let layout = settings_layout(&test_screen);
+        let grid_info = detect_grid(
+            &test_screen,
+            &layout.grid,
+            &layout.scale,
+            &mut DebugDump::default(),
+        )
+        .unwrap();
+        assert_eq!(grid_info.rows, 5);
+        assert_eq!(grid_info.cols, 5);
+    }
+
+    #[test]
+    fn test_grid_detect_6() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_6, ImreadModes::IMREAD_GRAYSCALE as i32)
+            .expect(format!("File {} not found", FILE_TEST_6).as_str());
+        let layout = settings_layout(&test_screen);
+        let grid_info = detect_grid(
+            &test_screen,
+            &layout.grid,
+            &layout.scale,
+            &mut DebugDump::default(),
+        )
+        .unwrap();
+        assert_eq!(grid_info.rows, 6);
+        assert_eq!(grid_info.cols, 6);
+    }
+
+    #[test]
+    fn test_buffer_detect_scaled() {
+        load_default_settings();
+        for resolution in TEST_RESOLUTIONS {
+            let test_screen = imread_scaled(FILE_TEST_6, ImreadModes::IMREAD_GRAYSCALE, resolution);
+            let layout = settings_layout(&test_screen);
+            let buffer_size = detect_buffer_size(
+                &test_screen,
+                &layout.buffer,
+                &layout.scale,
+                &mut DebugDump::default(),
+            )
+            .unwrap();
+            assert_eq!(buffer_size, 8, "buffer size at {:?}", resolution);
+        }
+    }
+
+    #[test]
+    fn test_grid_detect_scaled() {
+        load_default_settings();
+        for resolution in TEST_RESOLUTIONS {
+            let test_screen = imread_scaled(FILE_TEST_5, ImreadModes::IMREAD_GRAYSCALE, resolution);
+            let layout = settings_layout(&test_screen);
+            let grid_info = detect_grid(
+                &test_screen,
+                &layout.grid,
+                &layout.scale,
+                &mut DebugDump::default(),
+            )
+            .unwrap();
+            assert_eq!(
+                (grid_info.rows, grid_info.cols),
+                (5, 5),
+                "grid size at {:?}",
+                resolution
+            );
+        }
+    }
+
+    #[test]
+    fn test_scan_puzzle_scaled() {
+        load_default_settings();
+        for resolution in TEST_RESOLUTIONS {
+            let test_screen =
+                imread_scaled(FILE_TEST_6_2, ImreadModes::IMREAD_UNCHANGED, resolution);
+            let puzzle = scan(&test_screen).unwrap().puzzle;
+            assert_eq!(puzzle.buffer_size, 8, "buffer size at {:?}", resolution);
+            assert_eq!(
+                puzzle.grid.cells, CELLS_TEST_6_2,
+                "grid cells at {:?}",
+                resolution
+            );
+            assert_eq!(
+                puzzle.daemons,
+                vec![
+                    vec!["1C", "55", "1C"],
+                    vec!["1C", "1C"],
+                    vec!["1C", "55", "1C"]
+                ],
+                "daemons at {:?}",
+                resolution
+            );
+        }
+    }
+
+    #[test]
+    fn test_located_layout() {
+        load_default_settings();
+        for (file, buffer_size, grid_size) in [
+            (FILE_TEST_4_DAEMONS, 7, 5),
+            (FILE_TEST_5, 8, 5),
+            (FILE_TEST_6, 8, 6),
+        ] {
+            let test_screen = imread(file, ImreadModes::IMREAD_GRAYSCALE as i32)
+                .expect(format!("File {} not found", file).as_str());
+            let frame_scale = FrameScale::new(test_screen.size().unwrap());
+            let layout = PanelLayout::locate(&test_screen, &frame_scale).unwrap();
+            assert_eq!(
+                detect_buffer_size(
+                    &test_screen,
+                    &layout.buffer,
+                    &layout.scale,
+                    &mut DebugDump::default()
+                )
+                .unwrap(),
+                buffer_size,
+                "buffer size of {}",
+                file
+            );
+            let grid_info = detect_grid(
+                &test_screen,
+                &layout.grid,
+                &layout.scale,
+                &mut DebugDump::default(),
+            )
+            .unwrap();
+            assert_eq!(
+                (grid_info.rows, grid_info.cols),
+                (grid_size, grid_size),
+                "grid size of {}",
+                file
+            );
+        }
+    }
+
+    #[test]
+    fn test_scan_puzzle_letterboxed() {
+        load_default_settings();
+        // Ultrawide screen with the 16:9 UI centered
+        let test_screen = imread(FILE_TEST_6_2, ImreadModes::IMREAD_UNCHANGED as i32)
+            .expect(format!("File {} not found", FILE_TEST_6_2).as_str());
+        let mut ultrawide_screen = Mat::default();
+        let black = cv::Scalar::new(0.0, 0.0, 0.0, 255.0);
+        cv::copy_make_border(
+            &test_screen,
+            &mut ultrawide_screen,
+            0,
+            0,
+            320,
+            320,
+            cv::BORDER_CONSTANT,
+            black,
+        )
+        .unwrap();
+        let result = scan(&ultrawide_screen).unwrap();
+        // The solution is shown over the code matrix, moved with the UI
+        let grid_area = scan(&test_screen).unwrap().grid_area;
+        assert_eq!(result.grid_area, grid_area + cv::Point::new(320, 0));
+        let puzzle = result.puzzle;
+        assert_eq!(puzzle.buffer_size, 8);
+        assert_eq!(puzzle.grid.rows, 6);
+        assert_eq!(
+            puzzle.daemons,
+            vec![
+                vec!["1C", "55", "1C"],
+                vec!["1C", "1C"],
+                vec!["1C", "55", "1C"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_puzzle_5() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_5, ImreadModes::IMREAD_UNCHANGED as i32)
+            .expect(format!("File {} not found", FILE_TEST_5).as_str());
+        let puzzle = scan(&test_screen).unwrap().puzzle;
+        assert_eq!(puzzle.grid.cells, CELLS_TEST_5);
+    }
+
+    #[test]
+    fn test_scan_puzzle_6() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_6, ImreadModes::IMREAD_UNCHANGED as i32)
+            .expect(format!("File {} not found", FILE_TEST_6).as_str());
+        let puzzle = scan(&test_screen).unwrap().puzzle;
+        assert_eq!(puzzle.grid.cells, CELLS_TEST_6);
+    }
+
+    #[test]
+    fn test_scan_puzzle_6_2() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_6_2, ImreadModes::IMREAD_UNCHANGED as i32)
+            .expect(format!("File {} not found", FILE_TEST_6_2).as_str());
+        let puzzle = scan(&test_screen).unwrap().puzzle;
+        assert_eq!(puzzle.grid.cells, CELLS_TEST_6_2);
+        assert_eq!(
+            puzzle.daemons,
+            vec![
+                vec!["1C", "55", "1C"],
+                vec!["1C", "1C"],
+                vec!["1C", "55", "1C"]
+            ]
+        );
+    }
+
+    /// Compare the scan time of a new scanner per screenshot with a scanner reused for all of them.
+    /// Run with `cargo test --release benchmark_scanner_reuse -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn benchmark_scanner_reuse() {
+        load_default_settings();
+        let screens: Vec<Mat> = [FILE_TEST_5, FILE_TEST_6, FILE_TEST_6_2]
+            .iter()
+            .map(|file| {
+                imread(file, ImreadModes::IMREAD_UNCHANGED as i32)
+                    .expect(format!("File {} not found", file).as_str())
+            })
+            .collect();
+        let start = Instant::now();
+        let new_puzzles: Vec<Puzzle> = screens
+            .iter()
+            .map(|screen| scan(screen).unwrap().puzzle)
+            .collect();
+        let new_elapsed = start.elapsed();
+        let start = Instant::now();
+        let mut scanner = Scanner::new().unwrap();
+        let reused_puzzles: Vec<Puzzle> = screens
+            .iter()
+            .map(|screen| scanner.scan(screen).unwrap().puzzle)
+            .collect();
+        let reused_elapsed = start.elapsed();
+        assert_eq!(reused_puzzles, new_puzzles);
+        let per_scan = |elapsed: Duration| elapsed.as_secs_f64() * 1000.0 / screens.len() as f64;
+        println!(
+            "new scanner: {:.1} ms per scan, reused scanner: {:.1} ms per scan",
+            per_scan(new_elapsed),
+            per_scan(reused_elapsed)
+        );
+        // The engines and templates are only loaded once, including the reused scanner creation
+        assert!(
+            reused_elapsed < new_elapsed,
+            "reused scanner is not faster: {:?} against {:?}",
+            reused_elapsed,
+            new_elapsed
+        );
+    }
+
+    #[test]
+    fn test_scan_blank_screen() {
+        load_default_settings();
+        let blank_screen =
+            Mat::new_rows_cols_with_default(1080, 1920, cv::CV_8UC3, cv::Scalar::all(0.0)).unwrap();
+        match scan(&blank_screen) {
+            Err(ScanError::BufferNotFound { rect }) => {
+                assert_eq!(rect, settings_layout(&blank_screen).buffer)
+            }
+            result => panic!("Expected missing buffer, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_scan_debug_dump() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_6, ImreadModes::IMREAD_UNCHANGED as i32)
+            .expect(format!("File {} not found", FILE_TEST_6).as_str());
+        let dir = tempfile::tempdir().unwrap();
+        let mut debug = DebugDump::new(dir.path()).unwrap();
+        let mut scanner = Scanner::new().unwrap();
+        scanner.scan_debug(&test_screen, &mut debug).unwrap();
+
+        for image in [
+            "layout.png",
+            "buffer_match.png",
+            "buffer_slots.png",
+            "grid_threshold.png",
+            "grid_rows.png",
+            "grid_cols.png",
+            "grid_cells.png",
+            "cells/r1c1.png",
+            "cells/r1c1_ocr.png",
+            "cells/r6c6.png",
+            "daemons/1_1.png",
+        ] {
+            assert!(dir.path().join(image).is_file(), "missing {}", image);
+        }
+        let summary: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(dir.path().join("summary.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(summary["layout_from_settings"], false);
+        assert_eq!(summary["buffer_size"], 8);
+        assert!(summary["buffer_codes"].as_array().unwrap().is_empty());
+        assert_eq!(summary["grid_rows"], 6);
+        assert!(summary["used_cells"].as_array().unwrap().is_empty());
+        assert_eq!(summary["cells"][0]["code"], "E9");
+        assert!(summary["error"].is_null());
+    }
+
+    #[test]
+    fn test_scan_daemons() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_4_DAEMONS, ImreadModes::IMREAD_GRAYSCALE as i32)
+            .expect(format!("File {} not found", FILE_TEST_4_DAEMONS).as_str());
+        let mut reader = CellReader::new(ocr::recognizer().unwrap()).unwrap();
+        let layout = settings_layout(&test_screen);
+        let (daemons, confidence, _) = scan_daemons(
+            &mut reader,
+            &test_screen,
+            &layout.daemons,
+            &layout.daemon_rows,
+            None,
+            &layout.scale,
+            &mut DebugDump::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            daemons,
+            vec![
+                vec!["E9", "55"],
+                vec!["55", "BD", "E9"],
+                vec!["FF", "1C", "BD", "E9"],
+                vec!["55", "1C", "FF", "55"]
+            ]
+        );
+        let confidence_lengths: Vec<usize> = confidence.iter().map(Vec::len).collect();
+        assert_eq!(confidence_lengths, vec![2, 3, 4, 4]);
+    }
+
+    #[test]
+    fn test_scan_daemons_scripted() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_4_DAEMONS, ImreadModes::IMREAD_GRAYSCALE as i32)
+            .expect(format!("File {} not found", FILE_TEST_4_DAEMONS).as_str());
+        let layout = settings_layout(&test_screen);
+        let mut reader = cell_reader(ScriptedRecognizer::new(&[
+            ("E9", 90),
+            ("55", 91),
+            ("55", 92),
+            ("BO", 60),
+            ("E9", 93),
+            ("FF", 94),
+            ("1C", 95),
+            ("BD", 96),
+            ("E9", 97),
+            ("55", 98),
+            ("1C", 99),
+            ("FF", 100),
+            ("55", 80),
+        ]));
+        let (daemons, confidence, _) = scan_daemons(
+            &mut reader,
+            &test_screen,
+            &layout.daemons,
+            &layout.daemon_rows,
+            None,
+            &layout.scale,
+            &mut DebugDump::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            daemons,
+            vec![
+                vec!["E9", "55"],
+                vec!["55", "BD", "E9"],
+                vec!["FF", "1C", "BD", "E9"],
+                vec!["55", "1C", "FF", "55"]
+            ]
+        );
+        assert_eq!(
+            confidence,
+            vec![
+                vec![90, 91],
+                vec![92, 60, 93],
+                vec![94, 95, 96, 97],
+                vec![98, 99, 100, 80]
+            ]
+        );
+
+        // A code still misread with every retry preprocessing fails the whole daemon row
+        let mut reader = cell_reader(ScriptedRecognizer::new(&[
+            ("E9", 90),
+            ("XX", 10),
+            ("X", 10),
+            ("XY", 10),
+            ("", 0),
+        ]));
+        match scan_daemons(
+            &mut reader,
+            &test_screen,
+            &layout.daemons,
+            &layout.daemon_rows,
+            None,
+            &layout.scale,
+            &mut DebugDump::default(),
+        ) {
+            Err(ScanError::DaemonRowUnreadable { rect, text }) => {
+                assert!((rect.y - layout.daemon_rows[0].y).abs() <= 5, "{:?}", rect);
+                assert_eq!(text, "XX");
+            }
+            result => panic!("Expected unreadable daemon row, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_scan_daemons_retry() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_4_DAEMONS, ImreadModes::IMREAD_GRAYSCALE as i32)
+            .expect(format!("File {} not found", FILE_TEST_4_DAEMONS).as_str());
+        let layout = settings_layout(&test_screen);
+        let cells = "E9 55 BD FF 1C 55"
+            .split(' ')
+            .map(|code| code.parse::<Code>().unwrap())
+            .collect();
+        let grid = PuzzleGrid::try_from_cells(2, 3, cells).unwrap();
+        // An invalid code and a code missing from the grid are read again
+        let mut reader = cell_reader(ScriptedRecognizer::new(&[
+            ("E9", 90),
+            ("XX", 10),
+            ("55", 85),
+            ("55", 92),
+            ("BD", 93),
+            ("7A", 60),
+            ("", 0),
+            ("E9", 80),
+            ("FF", 94),
+            ("1C", 95),
+            ("BD", 96),
+            ("E9", 97),
+            ("55", 98),
+            ("1C", 99),
+            ("FF", 100),
+            ("55", 80),
+        ]));
+        let mut debug = DebugDump::default();
+        let (daemons, confidence, _) = scan_daemons(
+            &mut reader,
+            &test_screen,
+            &layout.daemons,
+            &layout.daemon_rows,
+            Some(&grid),
+            &layout.scale,
+            &mut debug,
+        )
+        .unwrap();
+        assert_eq!(daemons[0], vec!["E9", "55"]);
+        assert_eq!(daemons[1], vec!["55", "BD", "E9"]);
+        assert_eq!(confidence[1], vec![92, 93, 80]);
+        // Each failed cell is listed with the reading which replaced it
+        assert_eq!(debug.summary.retries.len(), 4);
+        assert!(debug.summary.retries[1].contains("read again as 55"));
+        assert!(debug.summary.retries[3].contains("read again as E9"));
+    }
+
+    #[test]
+    fn test_detect_daemons() {
+        load_default_settings();
+        for (file, lengths) in [
+            (FILE_TEST_4_DAEMONS, vec![2, 3, 4, 4]),
+            (FILE_TEST_5, vec![3, 3, 4]),
+            (FILE_TEST_6, vec![2, 2, 3]),
+            (FILE_TEST_6_2, vec![3, 2, 3]),
+        ] {
+            let test_screen = imread(file, ImreadModes::IMREAD_GRAYSCALE as i32)
+                .expect(format!("File {} not found", file).as_str());
+            let frame_scale = FrameScale::new(test_screen.size().unwrap());
+            let layout = PanelLayout::locate(&test_screen, &frame_scale).unwrap();
+            // No hint rows, so the rows must be detected
+            let daemons = detect_daemons(
+                &test_screen,
+                &layout.daemons,
+                &[],
+                &layout.scale,
+                &mut DebugDump::default(),
+            )
+            .unwrap();
+            let detected_lengths: Vec<u32> = daemons
+                .iter()
+                .map(|(_, cell_info)| cell_info.cols)
+                .collect();
+            assert_eq!(detected_lengths, lengths, "daemon lengths of {}", file);
+        }
+    }
+
+    /// Apply a contrast gain, a brightness offset and a gamma curve, like other display settings
+    fn adjust_levels(grey: &Mat, gain: f64, offset: f64, gamma: f64) -> Mat {
+        let mut lut =
+            Mat::new_rows_cols_with_default(1, 256, cv::CV_8UC1, cv::Scalar::all(0.0)).unwrap();
+        for value in 0..256 {
+            let adjusted = (value as f64 / 255.0).powf(gamma) * 255.0 * gain + offset;
+            *lut.at_mut::<u8>(value).unwrap() = adjusted.round().clamp(0.0, 255.0) as u8;
+        }
+        let mut adjusted = Mat::default();
+        cv::lut(grey, &lut, &mut adjusted).unwrap();
+        adjusted
+    }
+
+    #[test]
+    fn test_scan_level_variants() {
+        load_default_settings();
+        // (gain, offset, gamma): darker, washed out like HDR, brighter, lower and higher gamma
+        let variants = [
+            (0.5, 0.0, 1.0),
+            (0.8, 40.0, 1.0),
+            (1.3, -10.0, 1.0),
+            (1.0, 0.0, 0.6),
+            (1.0, 0.0, 1.6),
+        ];
+        let test_cases: [(&str, u32, &[&str], &[u32]); 3] = [
+            (FILE_TEST_5, 5, &CELLS_TEST_5, &[3, 3, 4]),
+            (FILE_TEST_6, 6, &CELLS_TEST_6, &[2, 2, 3]),
+            (FILE_TEST_6_2, 6, &CELLS_TEST_6_2, &[3, 2, 3]),
+        ];
+        let mut reader = cell_reader(GlyphClassifier::new().unwrap());
+        for (file, grid_size, cells, daemon_lengths) in test_cases {
+            let color_screen = imread(file, ImreadModes::IMREAD_COLOR as i32)
+                .expect(format!("File {} not found", file).as_str());
+            let mut test_screen = Mat::default();
+            imgproc::cvt_color(&color_screen, &mut test_screen, imgproc::COLOR_BGR2GRAY, 0)
+                .unwrap();
+            let mut screens: Vec<(String, Mat)> = variants
+                .iter()
+                .map(|&(gain, offset, gamma)| {
+                    let name = format!("gain {}, offset {}, gamma {}", gain, offset, gamma);
+                    (name, adjust_levels(&test_screen, gain, offset, gamma))
+                })
+                .collect();
+            // Colour filter dimming the yellow codes more than the background
+            let mut tinted = Mat::default();
+            cv::multiply(
+                &color_screen,
+                &cv::Scalar::new(1.2, 0.7, 0.5, 1.0),
+                &mut tinted,
+                1.0,
+                -1,
+            )
+            .unwrap();
+            let mut tinted_grey = Mat::default();
+            imgproc::cvt_color(&tinted, &mut tinted_grey, imgproc::COLOR_BGR2GRAY, 0).unwrap();
+            screens.push(("colour filter".to_string(), tinted_grey));
+
+            for (name, screen) in screens {
+                let variant = format!("{} with {}", file, name);
+                let frame_scale = FrameScale::new(screen.size().unwrap());
+                let layout = PanelLayout::locate(&screen, &frame_scale)
+                    .expect(format!("panel of {} not found", variant).as_str());
+                let scale = &layout.scale;
+                let mut debug = DebugDump::default();
+                let buffer_size = detect_buffer_size(&screen, &layout.buffer, scale, &mut debug);
+                assert_eq!(buffer_size.unwrap(), 8, "buffer size of {}", variant);
+                let grid_info = detect_grid(&screen, &layout.grid, scale, &mut debug).unwrap();
+                assert_eq!(
+                    (grid_info.rows, grid_info.cols),
+                    (grid_size, grid_size),
+                    "grid size of {}",
+                    variant
+                );
+                let (codes, _) =
+                    process_grid(&mut reader, &screen, &grid_info, scale, &mut debug).unwrap();
+                assert_eq!(codes, cells, "grid of {}", variant);
+                let daemons =
+                    detect_daemons(&screen, &layout.daemons, &[], scale, &mut debug).unwrap();
+                let lengths: Vec<u32> = daemons.iter().map(|(_, info)| info.cols).collect();
+                assert_eq!(lengths, daemon_lengths, "daemon lengths of {}", variant);
+            }
+        }
+    }
+
+    #[test]
+    fn test_scan_daemons_5x5() {
+        load_default_settings();
+        // Five sequences of five codes, more than the rows hinted in the settings
+        let mut test_screen =
+            Mat::new_rows_cols_with_default(1080, 1920, cv::CV_8UC1, cv::Scalar::all(0.0)).unwrap();
+        let template =
+            imread_asset("assets/templates/55.png", ImreadModes::IMREAD_GRAYSCALE).unwrap();
+        let mut glyph = Mat::default();
+        let glyph_size = cv::Size::new(26, 17);
+        imgproc::resize(
+            &template,
+            &mut glyph,
+            glyph_size,
+            0.0,
+            0.0,
+            imgproc::INTER_AREA,
+        )
+        .unwrap();
+        for row in 0..5 {
+            for col in 0..5 {
+                let position = cv::Point::new(845 + 43 * col, 349 + 70 * row);
+                let mut cell = Mat::roi(
+                    &test_screen,
+                    cv::Rect::from_point_size(position, glyph_size),
+                )
+                .unwrap();
+                glyph.copy_to(&mut cell).unwrap();
+            }
+        }
+
+        let layout = settings_layout(&test_screen);
+        let mut reader = cell_reader(GlyphClassifier::new().unwrap());
+        let (daemons, _, _) = scan_daemons(
+            &mut reader,
+            &test_screen,
+            &layout.daemons,
+            &layout.daemon_rows,
+            None,
+            &layout.scale,
+            &mut DebugDump::default(),
+        )
+        .unwrap();
+        assert_eq!(daemons, vec![vec!["55"; 5]; 5]);
+    }
+
+    /// Draw the glyphs of a code centered on a point, as white on black
+    fn paste_glyph(screen: &mut Mat, code: &str, center: cv::Point) {
+        let path = format!("assets/templates/{}.png", code);
+        let template = imread_asset(&path, ImreadModes::IMREAD_GRAYSCALE).unwrap();
+        let mut glyph = Mat::default();
+        let glyph_size = cv::Size::new(26, 17);
+        imgproc::resize(
+            &template,
+            &mut glyph,
+            glyph_size,
+            0.0,
+            0.0,
+            imgproc::INTER_AREA,
+        )
+        .unwrap();
+        let corner = cv::Point::new(
+            center.x - glyph_size.width / 2,
+            center.y - glyph_size.height / 2,
+        );
+        let mut target = Mat::roi(screen, cv::Rect::from_point_size(corner, glyph_size)).unwrap();
+        glyph.copy_to(&mut target).unwrap();
+    }
+
+    #[test]
+    fn test_scan_partial_state() {
+        load_default_settings();
+        let mut test_screen = imread(FILE_TEST_4_DAEMONS, ImreadModes::IMREAD_GRAYSCALE as i32)
+            .expect(format!("File {} not found", FILE_TEST_4_DAEMONS).as_str());
+        let layout = settings_layout(&test_screen);
+        let scale = &layout.scale;
+        let mut debug = DebugDump::default();
+        let no_mask = cv::no_array().unwrap();
+        let background = cv::Scalar::all(16.0);
+        let highlight = cv::Scalar::all(44.0);
+
+        let template = imread_asset(BUFFER_TEMPLATE, ImreadModes::IMREAD_GRAYSCALE).unwrap();
+        let match_threshold = settings().opencv.buffer_match_threshold;
+
+        // A fresh puzzle starts from the first row
+        let slots = detect_buffer_slots(
+            &test_screen,
+            &layout.buffer,
+            &template,
+            match_threshold,
+            scale,
+            &mut debug,
+        )
+        .unwrap();
+        assert_eq!(slots.len(), 7);
+        let buffer_size = detect_buffer_size(&test_screen, &layout.buffer, scale, &mut debug);
+        assert_eq!(buffer_size.unwrap(), 7);
+        let grid_info = detect_grid(&test_screen, &layout.grid, scale, &mut debug).unwrap();
+        assert!(grid_info.used.iter().all(|&used| !used));
+        assert_eq!(
+            detect_active_line(&test_screen, &grid_info).unwrap(),
+            Some(PuzzleMove::Row(0))
+        );
+
+        // Play 55 from the first row, then BD from the second column
+        for (slot, code) in slots.iter().zip(["55", "BD"]) {
+            let mut slot_image = Mat::roi(&test_screen, *slot).unwrap();
+            slot_image.set_to(&background, &no_mask).unwrap();
+            let center = cv::Point::new(slot.x + slot.width / 2, slot.y + slot.height / 2);
+            paste_glyph(&mut test_screen, code, center);
+        }
+        // Move the highlight from the first row to the third one
+        let cols = grid_info.cols as usize;
+        let band = |row: usize| union_rect(grid_info.cells[row * cols..(row + 1) * cols].iter());
+        let mut mask = Mat::default();
+        let mut first_row = Mat::roi(&test_screen, band(0)).unwrap();
+        cv::in_range(
+            &first_row,
+            &cv::Scalar::all(30.0),
+            &cv::Scalar::all(60.0),
+            &mut mask,
+        )
+        .unwrap();
+        first_row.set_to(&background, &mask).unwrap();
+        let mut third_row = Mat::roi(&test_screen, band(2)).unwrap();
+        cv::in_range(
+            &third_row,
+            &cv::Scalar::all(0.0),
+            &cv::Scalar::all(25.0),
+            &mut mask,
+        )
+        .unwrap();
+        third_row.set_to(&highlight, &mask).unwrap();
+        // Blank the selected cells
+        for (row, col) in [(0, 1), (2, 1)] {
+            let level = median_grey(&test_screen, &band(row)).unwrap();
+            let mut cell = Mat::roi(&test_screen, grid_info.cells[row * cols + col]).unwrap();
+            cell.set_to(&cv::Scalar::all(level as f64), &no_mask)
+                .unwrap();
+        }
+
+        let mut reader = cell_reader(GlyphClassifier::new().unwrap());
+        let (buffer, buffer_size) = scan_buffer(
+            &mut reader,
+            &test_screen,
+            &layout.buffer,
+            &template,
+            match_threshold,
+            scale,
+            &mut debug,
+        )
+        .unwrap();
+        assert_eq!(buffer, vec!["55", "BD"]);
+        assert_eq!(buffer_size, 7);
+        let grid_info = detect_grid(&test_screen, &layout.grid, scale, &mut debug).unwrap();
+        let used: Vec<usize> = grid_info
+            .used
+            .iter()
+            .enumerate()
+            .filter(|(_, &used)| used)
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(used, vec![1, 2 * cols + 1]);
+        assert_eq!(
+            detect_active_line(&test_screen, &grid_info).unwrap(),
+            Some(PuzzleMove::Row(2))
+        );
+    }
+
+    #[test]
+    fn test_read_daemon_types_scripted() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_4_DAEMONS, ImreadModes::IMREAD_GRAYSCALE as i32)
+            .expect(format!("File {} not found", FILE_TEST_4_DAEMONS).as_str());
+        let layout = settings_layout(&test_screen);
+        let rows: Vec<cv::Rect> = detect_daemons(
+            &test_screen,
+            &layout.daemons,
+            &layout.daemon_rows,
+            &layout.scale,
+            &mut DebugDump::default(),
+        )
+        .unwrap()
+        .into_iter()
+        .map(|(row, _)| row)
+        .collect();
+        let mut ocr = ScriptedRecognizer::new(&[
+            ("ESTRAZIONE DATI_V1", 90),
+            ("ESTRAZIONE DATI V2", 90),
+            ("ESTRAZIONE DATl_V3", 90),
+            ("ESTRAZIONE DATI: COPIA MALWARE", 90),
+        ]);
+        let daemon_types = read_daemon_types(
+            &mut ocr,
+            &DaemonNames::from_settings().unwrap(),
+            &test_screen,
+            &rows,
+            &layout.daemon_names,
+            &layout.scale,
+            &mut DebugDump::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            daemon_types,
+            vec![
+                DaemonType::DatamineV1,
+                DaemonType::DatamineV2,
+                DaemonType::DatamineV3,
+                DaemonType::DatamineCopyMalware
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tesseract")]
+    fn test_scan_daemon_names() {
+        load_default_settings();
+        for (file, daemon_types) in [
+            (
+                FILE_TEST_4_DAEMONS,
+                vec![
+                    DaemonType::DatamineV1,
+                    DaemonType::DatamineV2,
+                    DaemonType::DatamineV3,
+                    DaemonType::DatamineCopyMalware,
+                ],
+            ),
+            (
+                FILE_TEST_6,
+                vec![
+                    DaemonType::DatamineV1,
+                    DaemonType::DatamineV2,
+                    DaemonType::DatamineV3,
+                ],
+            ),
+        ] {
+            let test_screen = imread(file, ImreadModes::IMREAD_UNCHANGED as i32)
+                .expect(format!("File {} not found", file).as_str());
+            let puzzle = scan(&test_screen).unwrap().puzzle;
+            assert_eq!(
+                puzzle.daemon_types, daemon_types,
+                "daemon types of {}",
+                file
+            );
+        }
+    }
+
+    /// Read the grid cells of the test screenshots, returning the number of cells read correctly,
+    /// the number of cells and the reading time
+    fn read_test_cells(ocr: Box<dyn CellRecognizer>) -> (usize, usize, Duration) {
+        let test_cases: [(&str, &[&str]); 3] = [
+            (FILE_TEST_5, &CELLS_TEST_5),
+            (FILE_TEST_6, &CELLS_TEST_6),
+            (FILE_TEST_6_2, &CELLS_TEST_6_2),
+        ];
+        let mut reader = CellReader::new(ocr).unwrap();
+        let mut cells = 0;
+        let mut correct = 0;
+        let mut elapsed = Duration::ZERO;
+        for (file, expected_cells) in test_cases {
+            let test_screen = imread(file, ImreadModes::IMREAD_GRAYSCALE as i32)
+                .expect(format!("File {} not found", file).as_str());
+            let layout = settings_layout(&test_screen);
+            let grid_info = detect_grid(
+                &test_screen,
+                &layout.grid,
+                &layout.scale,
+                &mut DebugDump::default(),
+            )
+            .unwrap();
+            for (cell, expected) in grid_info.cells.iter().zip(expected_cells) {
+                let start = Instant::now();
+                let result = extract_cell(
+                    &mut reader,
+                    &test_screen,
+                    cell,
+                    &layout.scale,
+                    &mut DebugDump::default(),
+                    "cell",
+                );
+                elapsed += start.elapsed();
+                cells += 1;
+                if matches!(result, Ok((code, _)) if code == *expected) {
+                    correct += 1;
+                }
+            }
+        }
+        (correct, cells, elapsed)
+    }
+
+    #[test]
+    fn test_template_ocr() {
+        load_default_settings();
+        let (correct, cells, _) = read_test_cells(Box::new(GlyphClassifier::new().unwrap()));
+        assert_eq!(correct, cells, "template matching accuracy");
+    }
+
+    /// Compare accuracy and speed of the OCR engines on the grid cells of the test screenshots.
+    /// Run with `cargo test --release benchmark_ocr_engines -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn benchmark_ocr_engines() {
+        load_default_settings();
+        let mut engines: Vec<(&str, Box<dyn CellRecognizer>)> =
+            vec![("templates", Box::new(GlyphClassifier::new().unwrap()))];
+        #[cfg(feature = "tesseract")]
+        engines.insert(
+            0,
+            (
+                "tesseract",
+                Box::new(ocr::TesseractRecognizer::new().unwrap()),
+            ),
+        );
+        for (name, ocr) in engines {
+            let (correct, cells, elapsed) = read_test_cells(ocr);
+            println!(
+                "{}: {}/{} cells correct, {:.2} ms per cell",
+                name,
+                correct,
+                cells,
+                elapsed.as_secs_f64() * 1000.0 / cells as f64
+            );
+        }
+    }
+
+    /// Compare the time to read the code matrix with one engine and with a pool of engines.
+    /// Run with `cargo test --release benchmark_ocr_pool -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn benchmark_ocr_pool() {
+        load_default_settings();
+        let test_screen = imread(FILE_TEST_6, ImreadModes::IMREAD_GRAYSCALE as i32)
+            .expect(format!("File {} not found", FILE_TEST_6).as_str());
+        let layout = settings_layout(&test_screen);
+        let grid_info = detect_grid(
+            &test_screen,
+            &layout.grid,
+            &layout.scale,
+            &mut DebugDump::default(),
+        )
+        .unwrap();
+        let local = CellReader::new(ocr::recognizer().unwrap()).unwrap();
+        let pool = CellReader {
+            engines: CellEngines::Pool(OcrPool::new(4, ocr::recognizer).unwrap()),
+            valid_codes: settings().valid_codes.clone(),
+        };
+        for (name, mut reader) in [("single engine", local), ("pool of 4 engines", pool)] {
+            let start = Instant::now();
+            let (codes, _) = process_grid(
+                &mut reader,
+                &test_screen,
+                &grid_info,
+                &layout.scale,
+                &mut DebugDump::default(),
+            )
+            .unwrap();
+            println!("{}: {:.1} ms", name, start.elapsed().as_secs_f64() * 1000.0);
+            assert_eq!(codes, CELLS_TEST_6, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_uncertain_codes() {
+        let puzzle: Puzzle = "buffer: 4
+            grid:
+            1C 55
+            BD E9
+            daemons:
+            1C 55
+            BD"
+        .parse()
+        .unwrap();
+        let result = ScanResult {
+            puzzle,
+            grid_confidence: PuzzleGrid::try_from_cells(2, 2, vec![95u8, 40, 91, 88]).unwrap(),
+            daemon_confidence: vec![vec![93, 92], vec![12]],
+            min_confidence: 70,
+            state: PuzzleState::default(),
+            panel: cv::Rect::default(),
+            grid_area: cv::Rect::default(),
+        };
+        assert_eq!(result.uncertain_cells(), vec![(0, 1)]);
+        assert_eq!(result.uncertain_daemon_codes(), vec![(1, 0)]);
+        assert_eq!(
+            result.warnings(),
+            vec![
+                "Uncertain grid cell at row 1, column 2: 55 (40%)",
+                "Uncertain code 1 of daemon #2: BD (12%)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use super::ScanError;
+use crate::configuration::settings;
+use crate::types::DaemonType;
+
+/// Names of the known daemon types as shown in game, in any of the configured languages
+pub(crate) struct DaemonNames {
+    /// Normalized names with their daemon type
+    names: Vec<(String, DaemonType)>,
+}
+
+impl DaemonNames {
+    pub(crate) fn new(names: &[(&str, DaemonType)]) -> DaemonNames {
+        DaemonNames {
+            names: names
+                .iter()
+                .map(|&(name, daemon_type)| (normalize(name), daemon_type))
+                .collect(),
+        }
+    }
+
+    /// Names from the `daemon_names.types` setting
+    pub(crate) fn from_settings() -> Result<DaemonNames, ScanError> {
+        let settings = settings();
+        let mut names = Vec::new();
+        for type_cfg in settings.daemon_names.types.iter() {
+            let daemon_type = DaemonType::from_id(&type_cfg.daemon_type).ok_or_else(|| {
+                ScanError::Config(format!(
+                    "unknown daemon type \"{}\" in daemon_names",
+                    type_cfg.daemon_type
+                ))
+            })?;
+            for name in type_cfg.names.iter() {
+                names.push((name.as_str(), daemon_type));
+            }
+        }
+        Ok(DaemonNames::new(&names))
+    }
+
+    /// Type of the daemon with the closest name to the OCR text. Up to one wrong character every
+    /// five is tolerated, the type is unknown if no name is that close or if different types are
+    /// equally close.
+    pub(crate) fn classify(&self, text: &str) -> DaemonType {
+        let text = normalize(text);
+        if text.is_empty() {
+            return DaemonType::Unknown;
+        }
+        let mut best: Option<(usize, DaemonType)> = None;
+        let mut ambiguous = false;
+        for (name, daemon_type) in self.names.iter() {
+            let distance = edit_distance(&text, name);
+            if distance > name.chars().count() / 5 {
+                continue;
+            }
+            match best {
+                Some((best_distance, _)) if distance > best_distance => (),
+                Some((best_distance, best_type)) if distance == best_distance => {
+                    ambiguous |= best_type != *daemon_type;
+                }
+                _ => {
+                    best = Some((distance, *daemon_type));
+                    ambiguous = false;
+                }
+            }
+        }
+        match best {
+            Some((_, daemon_type)) if !ambiguous => daemon_type,
+            _ => DaemonType::Unknown,
+        }
+    }
+}
+
+/// Uppercase letters and digits of a name, since OCR often misses spaces and punctuation
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Number of characters to insert, remove or replace to turn a text into another
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = distances[0];
+        distances[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let replace = diagonal + usize::from(a_char != *b_char);
+            diagonal = distances[j + 1];
+            distances[j + 1] = replace.min(distances[j] + 1).min(diagonal + 1);
+        }
+    }
+    distances[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::tests::load_default_settings;
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("DATAMINEV1", "DATAMINEV1"), 0);
+        assert_eq!(edit_distance("DATAMINEV1", "DATAMINEV2"), 1);
+        assert_eq!(edit_distance("ICEPCK", "ICEPICK"), 1);
+        assert_eq!(edit_distance("", "ICEPICK"), 7);
+        assert_eq!(edit_distance("KITTEN", "SITTING"), 3);
+    }
+
+    #[test]
+    fn classify() {
+        let names = DaemonNames::new(&[
+            ("DATAMINE_V1", DaemonType::DatamineV1),
+            ("DATAMINE_V2", DaemonType::DatamineV2),
+            ("ESTRAZIONE DATI_V1", DaemonType::DatamineV1),
+            (
+                "ESTRAZIONE DATI: COPIA MALWARE",
+                DaemonType::DatamineCopyMalware,
+            ),
+            ("ICEPICK", DaemonType::IcePick),
+        ]);
+        assert_eq!(names.classify("DATAMINE_V2"), DaemonType::DatamineV2);
+        assert_eq!(names.classify("Estrazione dati V1"), DaemonType::DatamineV1);
+        assert_eq!(
+            names.classify("ESTRAZIONE DATI: COPIA MALWAKE"),
+            DaemonType::DatamineCopyMalware
+        );
+        assert_eq!(names.classify("ICEPICK."), DaemonType::IcePick);
+        // V1 and V2 are equally close
+        assert_eq!(names.classify("DATAMINE_VI"), DaemonType::Unknown);
+        assert_eq!(names.classify("CAMERA SHUTDOWN"), DaemonType::Unknown);
+        assert_eq!(names.classify(""), DaemonType::Unknown);
+    }
+
+    #[test]
+    fn settings_names() {
+        load_default_settings();
+        let names = DaemonNames::from_settings().unwrap();
+        assert_eq!(names.classify("DATAMINE_V3"), DaemonType::DatamineV3);
+        assert_eq!(names.classify("ESTRAZIONE DATI_V2"), DaemonType::DatamineV2);
+    }
+}
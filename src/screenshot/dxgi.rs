@@ -0,0 +1,113 @@
+use dxgcap::{CaptureError, DXGIManager};
+use opencv::core as cv;
+use opencv::prelude::*;
+use std::ffi::c_void;
+use std::ptr;
+use windows::Win32::Foundation::{BOOL, LPARAM, RECT};
+use windows::Win32::Graphics::Gdi::{
+    EnumDisplayMonitors, GetMonitorInfoW, HDC, HMONITOR, MONITORINFO, MONITORINFOF_PRIMARY,
+};
+
+use super::FrameSource;
+use crate::scanner::ScanError;
+
+/// Timeout waiting for a new frame of the screen, in milliseconds
+const FRAME_TIMEOUT_MS: u32 = 300;
+
+/// Capture of a monitor with the DXGI desktop duplication API
+pub(crate) struct DxgiSource {
+    manager: DXGIManager,
+    /// Top left corner of the monitor on the desktop
+    origin: cv::Point,
+    /// Last captured frame, repeated while the screen does not change
+    last_frame: Option<Mat>,
+}
+
+impl DxgiSource {
+    /// Capture of a monitor, 0 is the primary one
+    pub(crate) fn new(monitor: usize) -> Result<DxgiSource, ScanError> {
+        let origin = monitor_origin(monitor)
+            .ok_or_else(|| ScanError::Capture(format!("monitor {} not found", monitor)))?;
+        let mut manager = DXGIManager::new(FRAME_TIMEOUT_MS)
+            .map_err(|e| ScanError::Capture(format!("failed to start screen capture: {}", e)))?;
+        manager.set_capture_source_index(monitor);
+        Ok(DxgiSource {
+            manager,
+            origin,
+            last_frame: None,
+        })
+    }
+}
+
+impl FrameSource for DxgiSource {
+    fn next_frame(&mut self) -> Result<Option<Mat>, ScanError> {
+        let (mut bgra, (width, height)) = match self.manager.capture_frame_components() {
+            Ok(components) => components,
+            // No new frame is available while the screen does not change
+            Err(CaptureError::Timeout) if self.last_frame.is_some() => {
+                return Ok(self.last_frame.clone());
+            }
+            Err(error) => {
+                return Err(ScanError::Capture(format!(
+                    "screen capture failed: {:?}",
+                    error
+                )))
+            }
+        };
+        let ptr = bgra.as_mut_ptr() as *mut c_void;
+        let mat = unsafe {
+            Mat::new_rows_cols_with_data(
+                height as i32,
+                width as i32,
+                cv::CV_8UC4,
+                ptr,
+                cv::Mat_AUTO_STEP,
+            )
+        }?
+        .clone(); // Deep clone data to avoid dangling pointer
+
+        // debug_show("screenshot", &mat);
+        self.last_frame = Some(mat.clone());
+        Ok(Some(mat))
+    }
+
+    fn origin(&self) -> cv::Point {
+        self.origin
+    }
+}
+
+/// Top left corner of a monitor on the desktop. Monitors are numbered like the DXGI capture
+/// sources, the primary one first and then the others in the order of the system.
+fn monitor_origin(monitor: usize) -> Option<cv::Point> {
+    let mut monitors: Vec<(bool, RECT)> = Vec::new();
+    unsafe {
+        EnumDisplayMonitors(
+            HDC::default(),
+            ptr::null(),
+            Some(add_monitor),
+            LPARAM(&mut monitors as *mut _ as isize),
+        )
+    };
+    monitors.sort_by_key(|&(primary, _)| !primary);
+    monitors
+        .get(monitor)
+        .map(|(_, rect)| cv::Point::new(rect.left, rect.top))
+}
+
+/// Add a monitor with its area to the list given as data, marking the primary one
+unsafe extern "system" fn add_monitor(
+    monitor: HMONITOR,
+    _hdc: HDC,
+    _rect: *mut RECT,
+    data: LPARAM,
+) -> BOOL {
+    let monitors = &mut *(data.0 as *mut Vec<(bool, RECT)>);
+    let mut info = MONITORINFO {
+        cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+    if GetMonitorInfoW(monitor, &mut info).as_bool() {
+        monitors.push((info.dwFlags & MONITORINFOF_PRIMARY != 0, info.rcMonitor));
+    }
+    true.into()
+}
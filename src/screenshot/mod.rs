@@ -0,0 +1,154 @@
+#[cfg(target_os = "windows")]
+mod dxgi;
+mod replay;
+#[cfg(target_os = "linux")]
+mod xlib;
+
+use std::path::Path;
+
+use opencv::core as cv;
+use opencv::prelude::*;
+
+use crate::configuration::settings;
+use crate::layout::clamp_rect;
+use crate::scanner::ScanError;
+
+#[cfg(target_os = "windows")]
+pub(crate) use dxgi::DxgiSource;
+pub(crate) use replay::{is_image, list_images, DirectorySource, ImageFileSource, VideoSource};
+#[cfg(target_os = "linux")]
+pub(crate) use xlib::X11Source;
+
+/// Source of the frames watched for puzzles: the screen, or a replay of recorded frames
+pub(crate) trait FrameSource {
+    /// Next frame in BGR or BGRA, or `None` when a replay is over. A capture error may be
+    /// temporary, so the next frame can be asked for again.
+    fn next_frame(&mut self) -> Result<Option<Mat>, ScanError>;
+
+    /// Position of the top left corner of the frames on the desktop, to show the overlay at the
+    /// right place. Replayed frames are at the origin.
+    fn origin(&self) -> cv::Point {
+        cv::Point::new(0, 0)
+    }
+}
+
+/// Part of the desktop to capture, from the `capture` settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct CaptureTarget {
+    /// Index of the monitor, 0 is the primary one
+    pub monitor: usize,
+    /// Region of the monitor in pixels from its top left corner, or the whole monitor
+    pub region: Option<cv::Rect>,
+}
+
+impl CaptureTarget {
+    pub(crate) fn from_settings() -> CaptureTarget {
+        let settings = settings();
+        let cfg = &settings.capture;
+        // The region shape is checked when the settings are loaded
+        let region = match *cfg.region.as_slice() {
+            [x, y, width, height] => Some(cv::Rect::new(x, y, width, height)),
+            _ => None,
+        };
+        CaptureTarget {
+            monitor: cfg.monitor,
+            region,
+        }
+    }
+}
+
+/// Capture of the target monitor with the backend of the platform, cropped to the target region
+pub(crate) fn screen_source(target: &CaptureTarget) -> Result<Box<dyn FrameSource>, ScanError> {
+    let source = monitor_source(target.monitor)?;
+    Ok(match target.region {
+        Some(region) => Box::new(RegionSource::new(source, region)),
+        None => source,
+    })
+}
+
+#[cfg(target_os = "windows")]
+fn monitor_source(monitor: usize) -> Result<Box<dyn FrameSource>, ScanError> {
+    Ok(Box::new(DxgiSource::new(monitor)?))
+}
+
+#[cfg(target_os = "linux")]
+fn monitor_source(monitor: usize) -> Result<Box<dyn FrameSource>, ScanError> {
+    Ok(Box::new(X11Source::new(monitor)?))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn monitor_source(_monitor: usize) -> Result<Box<dyn FrameSource>, ScanError> {
+    Err(ScanError::Capture(
+        "unsupported platform for screen capture".to_string(),
+    ))
+}
+
+/// Frames of another source cropped to a region, such as the game window. Scans are relative to
+/// the region, which is offset from the origin of the source.
+pub(crate) struct RegionSource {
+    source: Box<dyn FrameSource>,
+    region: cv::Rect,
+}
+
+impl RegionSource {
+    pub(crate) fn new(source: Box<dyn FrameSource>, region: cv::Rect) -> RegionSource {
+        RegionSource { source, region }
+    }
+}
+
+impl FrameSource for RegionSource {
+    fn next_frame(&mut self) -> Result<Option<Mat>, ScanError> {
+        let frame = match self.source.next_frame()? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        let size = frame.size()?;
+        if clamp_rect(self.region, size) != self.region {
+            return Err(ScanError::Capture(format!(
+                "capture region {:?} is outside of the {}x{} frame",
+                self.region, size.width, size.height
+            )));
+        }
+        // Copy the region, so that it does not keep the whole frame
+        Ok(Some(Mat::roi(&frame, self.region)?.clone()))
+    }
+
+    fn origin(&self) -> cv::Point {
+        self.source.origin() + self.region.tl()
+    }
+}
+
+/// Replay of recorded frames: a directory of image files played in the order of their names, a
+/// single image file, or any other file as a video
+pub(crate) fn replay_source(path: &Path) -> Result<Box<dyn FrameSource>, ScanError> {
+    if path.is_dir() {
+        Ok(Box::new(DirectorySource::new(path)?))
+    } else if is_image(path) {
+        Ok(Box::new(ImageFileSource::new(path)))
+    } else {
+        Ok(Box::new(VideoSource::new(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_region() {
+        let mut source = RegionSource::new(
+            Box::new(ImageFileSource::new(Path::new("test/test_6x6.png"))),
+            cv::Rect::new(305, 190, 915, 710),
+        );
+        let frame = source.next_frame().unwrap().unwrap();
+        assert_eq!(frame.size().unwrap(), cv::Size::new(915, 710));
+        assert_eq!(source.origin(), cv::Point::new(305, 190));
+        assert!(source.next_frame().unwrap().is_none());
+
+        let mut outside = RegionSource::new(
+            Box::new(ImageFileSource::new(Path::new("test/test_6x6.png"))),
+            cv::Rect::new(1800, 0, 200, 100),
+        );
+        assert!(matches!(outside.next_frame(), Err(ScanError::Capture(_))));
+    }
+}
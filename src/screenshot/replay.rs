@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use opencv::imgcodecs::{imread, ImreadModes};
+use opencv::prelude::*;
+use opencv::videoio;
+
+use super::FrameSource;
+use crate::scanner::ScanError;
+
+/// Image extensions of recorded frames, other files are replayed as videos
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+/// Check if the file has one of the image extensions
+pub(crate) fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| {
+            IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str())
+        })
+}
+
+/// Image files of a directory, sorted by name
+pub(crate) fn list_images(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut images: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| is_image(path))
+        .collect();
+    images.sort();
+    Ok(images)
+}
+
+fn read_frame(path: &Path) -> Result<Mat, ScanError> {
+    let frame = imread(
+        &path.to_string_lossy(),
+        ImreadModes::IMREAD_UNCHANGED as i32,
+    )?;
+    if frame.empty()? {
+        return Err(ScanError::Capture(format!(
+            "failed to read image {}",
+            path.display()
+        )));
+    }
+    Ok(frame)
+}
+
+/// Single image file, replayed as one frame
+pub(crate) struct ImageFileSource {
+    path: Option<PathBuf>,
+}
+
+impl ImageFileSource {
+    pub(crate) fn new(path: &Path) -> ImageFileSource {
+        ImageFileSource {
+            path: Some(path.to_path_buf()),
+        }
+    }
+}
+
+impl FrameSource for ImageFileSource {
+    fn next_frame(&mut self) -> Result<Option<Mat>, ScanError> {
+        self.path.take().map(|path| read_frame(&path)).transpose()
+    }
+}
+
+/// Image files of a directory, replayed in the order of their names
+pub(crate) struct DirectorySource {
+    frames: std::vec::IntoIter<PathBuf>,
+}
+
+impl DirectorySource {
+    pub(crate) fn new(dir: &Path) -> Result<DirectorySource, ScanError> {
+        let frames = list_images(dir).map_err(|e| {
+            ScanError::Capture(format!("failed to list images in {}: {}", dir.display(), e))
+        })?;
+        Ok(DirectorySource {
+            frames: frames.into_iter(),
+        })
+    }
+}
+
+impl FrameSource for DirectorySource {
+    /// Frames which cannot be read are reported as capture errors and skipped
+    fn next_frame(&mut self) -> Result<Option<Mat>, ScanError> {
+        self.frames.next().map(|path| read_frame(&path)).transpose()
+    }
+}
+
+/// Frames of a recorded video file, decoded with OpenCV
+pub(crate) struct VideoSource {
+    video: videoio::VideoCapture,
+}
+
+impl VideoSource {
+    pub(crate) fn new(path: &Path) -> Result<VideoSource, ScanError> {
+        let video = videoio::VideoCapture::from_file(&path.to_string_lossy(), videoio::CAP_ANY)?;
+        if !video.is_opened()? {
+            return Err(ScanError::Capture(format!(
+                "failed to open video {}",
+                path.display()
+            )));
+        }
+        Ok(VideoSource { video })
+    }
+}
+
+impl FrameSource for VideoSource {
+    fn next_frame(&mut self) -> Result<Option<Mat>, ScanError> {
+        let mut frame = Mat::default();
+        if self.video.read(&mut frame)? && !frame.empty()? {
+            Ok(Some(frame))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::core as cv;
+    use opencv::imgcodecs::imwrite;
+
+    use super::*;
+
+    /// Frame sizes of a source until it ends
+    fn frame_sizes(source: &mut dyn FrameSource) -> Vec<(i32, i32)> {
+        let mut sizes = Vec::new();
+        while let Some(frame) = source.next_frame().unwrap() {
+            sizes.push((frame.cols(), frame.rows()));
+        }
+        sizes
+    }
+
+    #[test]
+    fn replay_images() {
+        let mut source = ImageFileSource::new(Path::new("test/test_5x5.jpg"));
+        assert_eq!(frame_sizes(&mut source), vec![(1920, 1080)]);
+        let mut missing = ImageFileSource::new(Path::new("test/missing.png"));
+        assert!(matches!(missing.next_frame(), Err(ScanError::Capture(_))));
+        assert!(missing.next_frame().unwrap().is_none());
+
+        // Frames are played by name, other files are left out
+        let dir = tempfile::tempdir().unwrap();
+        for (name, width) in [("2.png", 20), ("1.png", 10), ("3.PNG", 30)] {
+            let frame =
+                Mat::new_rows_cols_with_default(10, width, cv::CV_8UC3, cv::Scalar::all(0.0))
+                    .unwrap();
+            let path = dir.path().join(name);
+            imwrite(&path.to_string_lossy(), &frame, &cv::Vector::new()).unwrap();
+        }
+        fs::write(dir.path().join("notes.txt"), "not a frame").unwrap();
+        let mut source = DirectorySource::new(dir.path()).unwrap();
+        assert_eq!(frame_sizes(&mut source), vec![(10, 10), (20, 10), (30, 10)]);
+    }
+
+    #[test]
+    fn replay_video() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("breach.avi");
+        // Four character code of the Motion JPEG codec, built into OpenCV
+        let fourcc = i32::from_le_bytes(*b"MJPG");
+        let size = cv::Size::new(64, 48);
+        let mut writer =
+            videoio::VideoWriter::new(&path.to_string_lossy(), fourcc, 10.0, size, true).unwrap();
+        assert!(
+            writer.is_opened().unwrap(),
+            "MJPG video writer not available"
+        );
+        for level in [0.0, 128.0, 255.0] {
+            let frame =
+                Mat::new_size_with_default(size, cv::CV_8UC3, cv::Scalar::all(level)).unwrap();
+            writer.write(&frame).unwrap();
+        }
+        writer.release().unwrap();
+
+        let mut source = VideoSource::new(&path).unwrap();
+        assert_eq!(frame_sizes(&mut source), vec![(64, 48); 3]);
+        assert!(VideoSource::new(&dir.path().join("missing.avi")).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use opencv::core as cv;
+use opencv::prelude::*;
+use std::ffi::c_void;
+use std::ptr;
+use x11::{xinerama, xlib};
+
+use super::FrameSource;
+use crate::scanner::ScanError;
+
+/// Capture of a monitor of the X11 display named by `DISPLAY`, which shows the game when it runs
+/// through Proton. Frames are BGRA like the DXGI capture.
+pub(crate) struct X11Source {
+    display: *mut xlib::Display,
+    root: xlib::Window,
+    /// Area of the monitor in the root window
+    area: cv::Rect,
+}
+
+impl X11Source {
+    /// Capture of a Xinerama monitor, 0 is the first one. Without Xinerama the display has a
+    /// single monitor covering the root window.
+    pub(crate) fn new(monitor: usize) -> Result<X11Source, ScanError> {
+        let display = unsafe { xlib::XOpenDisplay(ptr::null()) };
+        if display.is_null() {
+            return Err(ScanError::Capture(
+                "failed to open the X11 display, check DISPLAY".to_string(),
+            ));
+        }
+        let (root, monitors) = unsafe {
+            let screen = xlib::XDefaultScreen(display);
+            (xlib::XRootWindow(display, screen), monitor_areas(display))
+        };
+        // The source closes the display when dropped, also on error
+        let mut source = X11Source {
+            display,
+            root,
+            area: cv::Rect::default(),
+        };
+        source.area = *monitors.get(monitor).ok_or_else(|| {
+            ScanError::Capture(format!(
+                "monitor {} not found, the display has {}",
+                monitor,
+                monitors.len()
+            ))
+        })?;
+        Ok(source)
+    }
+}
+
+/// Areas of the monitors of the display in the root window
+unsafe fn monitor_areas(display: *mut xlib::Display) -> Vec<cv::Rect> {
+    if xinerama::XineramaIsActive(display) != 0 {
+        let mut count = 0;
+        let screens = xinerama::XineramaQueryScreens(display, &mut count);
+        if !screens.is_null() {
+            let areas = std::slice::from_raw_parts(screens, count as usize)
+                .iter()
+                .map(|screen| {
+                    cv::Rect::new(
+                        screen.x_org as i32,
+                        screen.y_org as i32,
+                        screen.width as i32,
+                        screen.height as i32,
+                    )
+                })
+                .collect();
+            xlib::XFree(screens as *mut c_void);
+            return areas;
+        }
+    }
+    let screen = xlib::XDefaultScreen(display);
+    let width = xlib::XDisplayWidth(display, screen);
+    let height = xlib::XDisplayHeight(display, screen);
+    vec![cv::Rect::new(0, 0, width, height)]
+}
+
+impl FrameSource for X11Source {
+    fn next_frame(&mut self) -> Result<Option<Mat>, ScanError> {
+        let image = unsafe {
+            xlib::XGetImage(
+                self.display,
+                self.root,
+                self.area.x,
+                self.area.y,
+                self.area.width as u32,
+                self.area.height as u32,
+                xlib::XAllPlanes(),
+                xlib::ZPixmap,
+            )
+        };
+        if image.is_null() {
+            return Err(ScanError::Capture("X11 screen capture failed".to_string()));
+        }
+        let frame = unsafe { bgra_frame(&*image) };
+        unsafe { xlib::XDestroyImage(image) };
+        frame.map(Some)
+    }
+
+    fn origin(&self) -> cv::Point {
+        self.area.tl()
+    }
+}
+
+impl Drop for X11Source {
+    fn drop(&mut self) {
+        unsafe { xlib::XCloseDisplay(self.display) };
+    }
+}
+
+/// Copy of the pixels of a captured image. With 24 or 32 bit colour depth, pixels are 32 bits in
+/// BGRX byte order, the fourth byte is made opaque like the alpha of DXGI frames.
+unsafe fn bgra_frame(image: &xlib::XImage) -> Result<Mat, ScanError> {
+    if image.bits_per_pixel != 32 || image.byte_order != xlib::LSBFirst || image.depth < 24 {
+        return Err(ScanError::Capture(format!(
+            "unsupported X11 pixel format: depth {}, {} bits per pixel",
+            image.depth, image.bits_per_pixel
+        )));
+    }
+    let line = image.bytes_per_line as usize;
+    let pixels =
+        std::slice::from_raw_parts_mut(image.data as *mut u8, line * image.height as usize);
+    for row in pixels.chunks_exact_mut(line) {
+        for pixel in row[..4 * image.width as usize].chunks_exact_mut(4) {
+            pixel[3] = 255;
+        }
+    }
+    let frame = Mat::new_rows_cols_with_data(
+        image.height,
+        image.width,
+        cv::CV_8UC4,
+        image.data as *mut c_void,
+        line,
+    )?
+    .clone(); // Deep clone data, which is freed with the image
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::imgcodecs::{imread, ImreadModes};
+    use opencv::imgproc;
+
+    use super::*;
+
+    /// Draw a BGR image on the root window, at its top left corner
+    unsafe fn put_root_image(source: &X11Source, image: &Mat) {
+        let mut bgra = Mat::default();
+        imgproc::cvt_color(image, &mut bgra, imgproc::COLOR_BGR2BGRA, 0).unwrap();
+        let display = source.display;
+        let screen = xlib::XDefaultScreen(display);
+        let ximage = xlib::XCreateImage(
+            display,
+            xlib::XDefaultVisual(display, screen),
+            xlib::XDefaultDepth(display, screen) as u32,
+            xlib::ZPixmap,
+            0,
+            bgra.data_mut() as *mut _,
+            bgra.cols() as u32,
+            bgra.rows() as u32,
+            32,
+            0,
+        );
+        let gc = xlib::XDefaultGC(display, screen);
+        let (width, height) = (bgra.cols() as u32, bgra.rows() as u32);
+        xlib::XPutImage(display, source.root, gc, ximage, 0, 0, 0, 0, width, height);
+        xlib::XSync(display, xlib::False);
+        // The pixels belong to the Mat, not to the image
+        (*ximage).data = ptr::null_mut();
+        xlib::XDestroyImage(ximage);
+    }
+
+    /// Run under a virtual X server with
+    /// `xvfb-run -s "-screen 0 1920x1080x24" cargo test capture_x11 -- --ignored`
+    #[test]
+    #[ignore = "needs an X server, e.g. Xvfb"]
+    fn capture_x11() {
+        let mut source = X11Source::new(0).unwrap();
+        let screen = imread("test/test_6x6.png", ImreadModes::IMREAD_COLOR as i32).unwrap();
+        unsafe { put_root_image(&source, &screen) };
+
+        let frame = source.next_frame().unwrap().unwrap();
+        assert_eq!(frame.typ().unwrap(), cv::CV_8UC4);
+        assert!(frame.cols() >= screen.cols() && frame.rows() >= screen.rows());
+        // The captured colours are the ones of the image, and opaque
+        for y in 0..screen.rows() {
+            // A single row is continuous, even in a region of a larger image
+            let captured = frame.row(y).unwrap();
+            let captured = captured.data_typed::<cv::Vec4b>().unwrap();
+            let expected = screen.row(y).unwrap();
+            let expected = expected.data_typed::<cv::Vec3b>().unwrap();
+            for (pixel, bgr) in captured.iter().zip(expected) {
+                assert_eq!(pixel.0, [bgr[0], bgr[1], bgr[2], 255], "row {}", y);
+            }
+        }
+    }
+}
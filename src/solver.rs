@@ -14,7 +14,7 @@ enum DaemonMatchState {
 #[derive(Debug, Clone)]
 struct SolutionState {
     /// Vector with current Buffer state
-    buffer: Vec<String>,
+    buffer: Vec<Code>,
     /// Vector with current sequence of puzzle moves
     moves: PuzzleMoves,
     /// Move count for current step state
@@ -24,23 +24,73 @@ struct SolutionState {
     daemons: Vec<DaemonMatchState>,
     /// Next allowed move type for current state
     next_move_type: PuzzleMoveType,
+    /// Row or column active before the first move
+    start: PuzzleMove,
     /// Used cells map
     used_cells: HashMap<CellCoord, bool>,
 }
 
 impl SolutionState {
-    /// Create a new initial solution state for a puzzle
-    fn new(puzzle: &Puzzle) -> SolutionState {
+    /// Create the initial solution state of a puzzle, which may have been partially played
+    fn from_state(puzzle: &Puzzle, state: &PuzzleState) -> SolutionState {
         let buffer_size = puzzle.buffer_size.try_into().unwrap();
+        let mut buffer = Vec::with_capacity(buffer_size);
+        buffer.extend_from_slice(&state.buffer);
+        let mut daemons = vec![DaemonMatchState::Partial(0); puzzle.daemons.len()];
+        for code in state.buffer.iter() {
+            advance_daemons(puzzle, &mut daemons, code);
+        }
+        let next_move_type = match state.active {
+            PuzzleMove::Column(_) => PuzzleMoveType::SelectRow,
+            _ => PuzzleMoveType::SelectColumn,
+        };
+        // Cells shown as used in the grid are not selectable either
+        let grid = &puzzle.grid;
+        let used_grid_cells = (0..grid.rows)
+            .flat_map(|row| (0..grid.cols).map(move |col| (row, col)))
+            .filter(|&(row, col)| *grid.get_cell(row, col) == Cell::Used);
+        let used_cells = state
+            .used_cells
+            .iter()
+            .copied()
+            .chain(used_grid_cells)
+            .map(|cell| (cell, true))
+            .collect();
         Self {
-            buffer: Vec::with_capacity(buffer_size),
+            buffer,
             moves: Vec::with_capacity(buffer_size),
             move_count: 0,
-            daemons: vec![DaemonMatchState::Partial(0); puzzle.daemons.len()],
-            next_move_type: PuzzleMoveType::SelectColumn,
-            used_cells: HashMap::new(),
+            daemons,
+            next_move_type,
+            start: state.active,
+            used_cells,
+        }
+    }
+}
+
+/// Update the daemon match states with a new code in the buffer.
+/// Returns true if a daemon was completed by the code.
+fn advance_daemons(puzzle: &Puzzle, daemons: &mut [DaemonMatchState], code: &Code) -> bool {
+    let mut daemon_completed = false;
+    for (n, daemon) in puzzle.daemons.iter().enumerate() {
+        let daemon_len = daemon.len();
+        let match_state = &mut daemons[n];
+        // We can ignore already completed daemons and check only the remaining ones
+        if let DaemonMatchState::Partial(ref mut match_len) = *match_state {
+            // If cell matches daemon cell
+            if daemon[*match_len] == *code {
+                *match_len += 1;
+                if *match_len == daemon_len {
+                    *match_state = DaemonMatchState::Completed;
+                    daemon_completed = true;
+                }
+            } else {
+                // If not, reset match
+                *match_len = 0;
+            }
         }
     }
+    daemon_completed
 }
 
 impl std::fmt::Display for SolutionState {
@@ -59,21 +109,38 @@ impl std::fmt::Display for SolutionState {
 pub enum SolverSearchMethod {
     Shortest,
     FirstMatch,
+    /// Upload the daemons with the highest total priority, then prefer the shortest solution.
+    /// Unlike the other methods, a solution is found even if not all daemons can be uploaded.
+    Priority,
 }
 
 pub struct BreachSolver<'a> {
     puzzle: &'a Puzzle,
+    state: PuzzleState,
 }
 
 impl<'a> BreachSolver<'a> {
     pub fn new(puzzle: &'a Puzzle) -> BreachSolver<'a> {
-        BreachSolver { puzzle }
+        BreachSolver {
+            puzzle,
+            state: PuzzleState::default(),
+        }
+    }
+
+    /// Solver continuing a partially played puzzle
+    pub fn with_state(puzzle: &'a Puzzle, state: &PuzzleState) -> BreachSolver<'a> {
+        BreachSolver {
+            puzzle,
+            state: state.clone(),
+        }
     }
+
     pub fn solve(&self, method: SolverSearchMethod) -> Option<PuzzleSolution> {
-        let mut state = SolutionState::new(&self.puzzle);
+        let mut state = SolutionState::from_state(&self.puzzle, &self.state);
         let first_only = matches!(method, SolverSearchMethod::FirstMatch);
-        let mut solutions = self.step(&mut state, first_only);
-        // Sort solutions by length
+        let partial = matches!(method, SolverSearchMethod::Priority);
+        let mut solutions = self.step(&mut state, first_only, partial);
+        // Sort solutions by length, a partial search already returns only the best one
         solutions.sort_by_key(|solution| solution.moves.len());
         if let Some(solution) = solutions.get(0) {
             return Some(solution.to_owned());
@@ -81,21 +148,40 @@ impl<'a> BreachSolver<'a> {
         None
     }
     pub fn solve_all(&self) -> Vec<PuzzleSolution> {
-        let mut state = SolutionState::new(&self.puzzle);
-        let mut solutions = self.step(&mut state, false);
+        let mut state = SolutionState::from_state(&self.puzzle, &self.state);
+        let mut solutions = self.step(&mut state, false, false);
         // Sort solutions by length
         solutions.sort_by_key(|solution| solution.moves.len());
         solutions
     }
 
-    fn step(&self, state: &SolutionState, first_only: bool) -> Vec<PuzzleSolution> {
+    /// Total priority of the daemons uploaded by a solution, i.e. found in its buffer
+    pub fn priority(&self, solution: &PuzzleSolution) -> u32 {
+        self.puzzle
+            .daemons
+            .iter()
+            .enumerate()
+            .filter(|(_, daemon)| {
+                !daemon.is_empty()
+                    && solution
+                        .buffer
+                        .windows(daemon.len())
+                        .any(|codes| codes == daemon.as_slice())
+            })
+            .map(|(n, _)| self.puzzle.daemon_type(n).priority())
+            .sum()
+    }
+
+    /// Search solutions from the given state. With `partial`, solutions which upload only some
+    /// of the daemons are returned too, keeping only the best one of each search branch.
+    fn step(&self, state: &SolutionState, first_only: bool, partial: bool) -> Vec<PuzzleSolution> {
         // Current buffer/move index on which we are iterating in current search step
         let current_move_index: usize = state.move_count.try_into().unwrap();
         let mut solutions: Vec<PuzzleSolution> = Vec::new();
 
-        // Get last move from state, handle implicit first move = Row(0) when state has no moves yet
+        // Get last move from state, handle the active row or column when state has no moves yet
         let last_move = if current_move_index == 0 {
-            PuzzleMove::Row(0)
+            state.start
         } else {
             state.moves[current_move_index - 1]
         };
@@ -142,32 +228,20 @@ impl<'a> BreachSolver<'a> {
 
         // Try each available move
         for (new_move, (row, col)) in available_moves {
-            let cell_ref = self.puzzle.grid.get_cell(row, col);
+            let code = match self.puzzle.grid.get_cell(row, col) {
+                Cell::Code(code) => *code,
+                // Cells shown as used are never available
+                Cell::Used => continue,
+            };
 
             // Update cell usage
             new_state.moves.push(new_move);
-            new_state.buffer.push(cell_ref.to_string());
+            new_state.buffer.push(code);
             new_state.used_cells.insert((row, col), true);
 
             // Update daemon state
             // TODO: (perf) prune if any remaning match len is greater than remaining buffer size
-            for (n, daemon) in self.puzzle.daemons.iter().enumerate() {
-                let daemon_len = daemon.len();
-                let match_state = &mut new_state.daemons[n];
-                // We can ignore already completed daemons and check only the remaining ones
-                if let DaemonMatchState::Partial(ref mut match_len) = *match_state {
-                    // If cell matches daemon cell
-                    if *daemon[*match_len] == *cell_ref {
-                        *match_len += 1;
-                        if *match_len == daemon_len {
-                            *match_state = DaemonMatchState::Completed
-                        }
-                    } else {
-                        // If not, reset match
-                        *match_len = 0;
-                    }
-                }
-            }
+            let daemon_completed = advance_daemons(self.puzzle, &mut new_state.daemons, &code);
 
             // Check all daemons for completion
             let all_daemons_completed = new_state
@@ -179,15 +253,24 @@ impl<'a> BreachSolver<'a> {
                 solutions.push(PuzzleSolution {
                     moves: new_state.moves.clone(),
                     buffer: new_state.buffer.clone(),
+                    start: state.start,
                 });
                 if first_only {
                     // Stop searching for more solutions on first result
                     break;
                 }
             } else {
-                if new_state.move_count < self.puzzle.buffer_size {
+                if partial && daemon_completed {
+                    // Some daemons are uploaded, which may be the best we can do
+                    solutions.push(PuzzleSolution {
+                        moves: new_state.moves.clone(),
+                        buffer: new_state.buffer.clone(),
+                        start: state.start,
+                    });
+                }
+                if new_state.buffer.len() < self.puzzle.buffer_size as usize {
                     // Only if we can still move, recurse in depth with next move
-                    let mut rec_solutions = self.step(&new_state, first_only);
+                    let mut rec_solutions = self.step(&new_state, first_only, partial);
                     solutions.append(&mut rec_solutions);
                 }
             }
@@ -199,13 +282,23 @@ impl<'a> BreachSolver<'a> {
             new_state.used_cells.insert((row, col), false);
         }
 
+        if partial {
+            // Keep memory bounded, since most search branches upload some daemon
+            let best = solutions.into_iter().min_by_key(|solution| {
+                (
+                    std::cmp::Reverse(self.priority(solution)),
+                    solution.moves.len(),
+                )
+            });
+            return best.into_iter().collect();
+        }
         solutions
     }
 
-    pub fn to_grid(&self, solution: &PuzzleSolution) -> PuzzleGrid {
+    pub fn to_grid(&self, solution: &PuzzleSolution) -> PuzzleGrid<String> {
         let mut grid = PuzzleGrid::new(self.puzzle.grid.rows, self.puzzle.grid.cols);
         for (i, &(row, col)) in (*solution).to_coords().iter().enumerate() {
-            grid.set_cell(row, col, &(i + 1).to_string());
+            grid.set_cell(row, col, (i + 1).to_string());
         }
         grid
     }
@@ -218,8 +311,12 @@ mod tests {
     use super::*;
     use crate::types::PuzzleMove;
 
-    fn to_string_vector(v: Vec<&str>) -> Vec<String> {
-        v.iter().map(|s| s.to_string()).collect()
+    fn to_code_vector(v: Vec<&str>) -> Vec<Code> {
+        v.iter().map(|s| s.parse().unwrap()).collect()
+    }
+
+    fn to_code_grid(rows: u32, cols: u32, cells: Vec<&str>) -> PuzzleGrid {
+        PuzzleGrid::try_from_cells(rows, cols, to_code_vector(cells)).unwrap()
     }
 
     fn moves_to_u32_vec(moves: &PuzzleMoves) -> Vec<u32> {
@@ -238,11 +335,12 @@ mod tests {
         let test_puzzle_1: Puzzle = Puzzle {
             buffer_size: 8,
             daemons: vec![
-                to_string_vector(vec!["BD", "55", "1C"]),
-                to_string_vector(vec!["E9", "BD", "1C"]),
-                to_string_vector(vec!["1C", "55", "55", "BD"]),
+                to_code_vector(vec!["BD", "55", "1C"]),
+                to_code_vector(vec!["E9", "BD", "1C"]),
+                to_code_vector(vec!["1C", "55", "55", "BD"]),
             ],
-            grid: PuzzleGrid::from_cells(
+            daemon_types: vec![DaemonType::Unknown; 3],
+            grid: to_code_grid(
                 5,
                 5,
                 vec![
@@ -265,11 +363,12 @@ mod tests {
         let test_puzzle_2: Puzzle = Puzzle {
             buffer_size: 7,
             daemons: vec![
-                to_string_vector(vec!["1C", "55"]),
-                to_string_vector(vec!["55", "55", "55"]),
-                to_string_vector(vec!["1C", "1C", "BD"]),
+                to_code_vector(vec!["1C", "55"]),
+                to_code_vector(vec!["55", "55", "55"]),
+                to_code_vector(vec!["1C", "1C", "BD"]),
             ],
-            grid: PuzzleGrid::from_cells(
+            daemon_types: vec![DaemonType::Unknown; 3],
+            grid: to_code_grid(
                 5,
                 5,
                 vec![
@@ -325,16 +424,83 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_priority() {
+        // Both daemons cannot fit in the buffer, the most valuable one is uploaded
+        let text = "buffer: 3
+            grid:
+            1C 55 BD
+            55 E9 1C
+            BD 1C 55
+            daemons:
+            55 E9 # datamine_v1
+            BD 1C 55 # datamine_v3";
+        let mut puzzle: Puzzle = text.parse().unwrap();
+        let solver = BreachSolver::new(&puzzle);
+        assert!(solver.solve(SolverSearchMethod::Shortest).is_none());
+        let solution = solver.solve(SolverSearchMethod::Priority).unwrap();
+        assert_eq!(solution.buffer, vec!["BD", "1C", "55"]);
+        assert_eq!(solver.priority(&solution), 3);
+
+        // Quest daemons come first
+        puzzle.daemon_types = vec![DaemonType::DatamineCopyMalware, DaemonType::DatamineV3];
+        let solver = BreachSolver::new(&puzzle);
+        let solution = solver.solve(SolverSearchMethod::Priority).unwrap();
+        assert_eq!(solution.buffer, vec!["55", "E9"]);
+
+        // When all daemons fit, the shortest full solution is found
+        let puzzle: Puzzle = text.replace("buffer: 3", "buffer: 6").parse().unwrap();
+        let solver = BreachSolver::new(&puzzle);
+        let shortest = solver.solve(SolverSearchMethod::Shortest).unwrap();
+        let solution = solver.solve(SolverSearchMethod::Priority).unwrap();
+        assert_eq!(solution.moves.len(), shortest.moves.len());
+        assert_eq!(solver.priority(&solution), 4);
+    }
+
+    #[test]
+    fn test_continue_from_state() {
+        let puzzle: Puzzle = "buffer: 4
+            grid:
+            1C 55 BD
+            55 E9 1C
+            BD 1C 55
+            daemons:
+            55 E9 1C"
+            .parse()
+            .unwrap();
+        // 55 was selected in the first row, so the next code comes from the second column
+        let state = PuzzleState {
+            buffer: to_code_vector(vec!["55"]),
+            used_cells: vec![(0, 1)],
+            active: PuzzleMove::Column(1),
+        };
+        let solver = BreachSolver::with_state(&puzzle, &state);
+        let solution = solver.solve(SolverSearchMethod::Shortest).unwrap();
+        assert_eq!(moves_to_u32_vec(&solution.moves), vec![1, 2]);
+        assert_eq!(solution.buffer, vec!["55", "E9", "1C"]);
+        assert_eq!(solution.to_coords(), vec![(1, 1), (1, 2)]);
+
+        // Used cells are not selected again, 1C is left only in other rows
+        let state = PuzzleState {
+            buffer: to_code_vector(vec!["55", "E9"]),
+            used_cells: vec![(1, 0), (1, 1), (1, 2)],
+            active: PuzzleMove::Row(1),
+        };
+        let solver = BreachSolver::with_state(&puzzle, &state);
+        assert!(solver.solve(SolverSearchMethod::Shortest).is_none());
+    }
+
     // #[test]
     #[allow(dead_code)]
     fn test_debug_grid() {
         #[rustfmt::skip]
         let test_puzzle_2: Puzzle = Puzzle {
             buffer_size: 10,
-            daemons: vec![to_string_vector(vec![
+            daemons: vec![to_code_vector(vec![
                 "A1", "A2", "B2", "B3", "C3", "C4", "D4", "D5", "E5",
             ])],
-            grid: PuzzleGrid::from_cells(
+            daemon_types: vec![DaemonType::Unknown],
+            grid: to_code_grid(
                 5,
                 5,
                 vec![
@@ -5,7 +5,7 @@ pub enum PuzzleMoveType {
     SelectColumn,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum PuzzleMove {
     None,
     Row(u32),
@@ -19,13 +19,20 @@ pub type CellCoord = (u32, u32);
 
 #[derive(Clone)]
 pub struct PuzzleSolution {
-    pub buffer: Vec<String>,
+    /// Buffer after the moves, including the codes which were already in the buffer
+    pub buffer: Vec<Code>,
     pub moves: PuzzleMoves,
+    /// Row or column active before the first move, `Row(0)` unless the puzzle was partially played
+    pub start: PuzzleMove,
 }
 impl PuzzleSolution {
     pub fn to_coords(&self) -> Vec<CellCoord> {
         use crate::types::PuzzleMove::{Column, Row};
-        let mut last_coord = (0, 0);
+        let mut last_coord = match self.start {
+            Row(row) => (row, 0),
+            Column(col) => (0, col),
+            PuzzleMove::None => (0, 0),
+        };
         let mut coords = Vec::new();
         for cell in self.moves.iter() {
             let coord = match *cell {
@@ -47,38 +54,204 @@ impl std::fmt::Display for PuzzleSolution {
     }
 }
 
-pub struct PuzzleGrid {
+/// Puzzle cell code, a single byte displayed in hex notation (e.g. `1C`, `E9`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Code(u8);
+
+impl Code {
+    pub const fn new(value: u8) -> Code {
+        Code(value)
+    }
+
+    pub fn value(self) -> u8 {
+        self.0
+    }
+
+    /// Check that the code is one of the `valid` codes
+    pub fn validate(self, valid: &[Code]) -> Result<Code, CodeError> {
+        if valid.contains(&self) {
+            Ok(self)
+        } else {
+            Err(CodeError::NotAllowed(self))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodeError {
+    /// Codes are made of exactly two characters
+    InvalidLength(String),
+    /// Code characters must be hex digits
+    InvalidHex(String),
+    /// Code is well formed, but not in the set of valid codes
+    NotAllowed(Code),
+}
+
+impl std::fmt::Display for CodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodeError::InvalidLength(text) => {
+                write!(f, "code \"{}\" must be 2 characters long", text)
+            }
+            CodeError::InvalidHex(text) => write!(f, "code \"{}\" is not a hex number", text),
+            CodeError::NotAllowed(code) => write!(f, "code \"{}\" is not a valid code", code),
+        }
+    }
+}
+
+impl std::error::Error for CodeError {}
+
+impl std::str::FromStr for Code {
+    type Err = CodeError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if text.chars().count() != 2 {
+            return Err(CodeError::InvalidLength(text.to_string()));
+        }
+        if !text.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(CodeError::InvalidHex(text.to_string()));
+        }
+        let value =
+            u8::from_str_radix(text, 16).map_err(|_| CodeError::InvalidHex(text.to_string()))?;
+        Ok(Code(value))
+    }
+}
+
+impl std::fmt::Display for Code {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Use pad to honour width and alignment flags
+        f.pad(&format!("{:02X}", self.0))
+    }
+}
+
+/// Codes are written as their text in the settings
+impl<'de> serde::Deserialize<'de> for Code {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl PartialEq<str> for Code {
+    fn eq(&self, other: &str) -> bool {
+        other.parse::<Code>().map_or(false, |code| code == *self)
+    }
+}
+
+impl PartialEq<&str> for Code {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+/// Puzzle grid cell, showing its code until it is selected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cell {
+    Code(Code),
+    /// Cell which was already selected and shows no code, written as `--`
+    Used,
+}
+
+impl Cell {
+    /// Code of the cell, if it was not used yet
+    pub fn code(self) -> Option<Code> {
+        match self {
+            Cell::Code(code) => Some(code),
+            Cell::Used => None,
+        }
+    }
+}
+
+impl From<Code> for Cell {
+    fn from(code: Code) -> Self {
+        Cell::Code(code)
+    }
+}
+
+impl std::str::FromStr for Cell {
+    type Err = CodeError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "--" => Ok(Cell::Used),
+            _ => text.parse().map(Cell::Code),
+        }
+    }
+}
+
+impl std::fmt::Display for Cell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Cell::Code(code) => code.fmt(f),
+            Cell::Used => f.pad("--"),
+        }
+    }
+}
+
+impl PartialEq<Code> for Cell {
+    fn eq(&self, other: &Code) -> bool {
+        self.code() == Some(*other)
+    }
+}
+
+impl PartialEq<str> for Cell {
+    fn eq(&self, other: &str) -> bool {
+        other.parse::<Cell>().map_or(false, |cell| cell == *self)
+    }
+}
+
+impl PartialEq<&str> for Cell {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PuzzleGrid<T = Cell> {
     pub rows: u32,
     pub cols: u32,
-    pub cells: Vec<String>,
+    pub cells: Vec<T>,
 }
 
-impl PuzzleGrid {
-    pub fn new(rows: u32, cols: u32) -> PuzzleGrid {
+impl<T: Clone + Default> PuzzleGrid<T> {
+    pub fn new(rows: u32, cols: u32) -> PuzzleGrid<T> {
         let mut cells = Vec::new();
         cells.resize(
             std::convert::TryInto::try_into(rows * cols).unwrap(),
-            String::from(""),
+            T::default(),
         );
         PuzzleGrid { rows, cols, cells }
     }
+}
 
-    pub fn from_cells<S: Into<String>>(rows: u32, cols: u32, cells: Vec<S>) -> PuzzleGrid {
-        PuzzleGrid {
+impl<T> PuzzleGrid<T> {
+    /// Create a grid from row-major cells, checking that their number matches the grid size
+    pub fn try_from_cells<S: Into<T>>(
+        rows: u32,
+        cols: u32,
+        cells: Vec<S>,
+    ) -> Result<PuzzleGrid<T>, PuzzleError> {
+        if rows == 0 || cols == 0 || cells.len() != (rows * cols) as usize {
+            return Err(PuzzleError::GridSize {
+                rows,
+                cols,
+                cells: cells.len(),
+            });
+        }
+        Ok(PuzzleGrid {
             rows,
             cols,
             cells: cells.into_iter().map(|s| s.into()).collect(),
-        }
+        })
     }
 
-    pub fn row(&self, index: u32) -> Vec<&str> {
+    pub fn row(&self, index: u32) -> Vec<&T> {
         return self.cells[(index * self.cols) as usize..((index + 1) * self.cols) as usize]
             .iter()
-            .map(String::as_str)
             .collect();
     }
 
-    pub fn col(&self, index: u32) -> Vec<&str> {
+    pub fn col(&self, index: u32) -> Vec<&T> {
         let mut col = Vec::new();
         for cell in self
             .cells
@@ -86,21 +259,21 @@ impl PuzzleGrid {
             .skip(index as usize)
             .step_by(self.cols as usize)
         {
-            col.push(cell.as_str());
+            col.push(cell);
         }
         return col;
     }
 
-    pub fn get_cell(&self, row: u32, col: u32) -> &String {
+    pub fn get_cell(&self, row: u32, col: u32) -> &T {
         return &self.cells[(col + row * self.cols) as usize];
     }
 
-    pub fn set_cell(&mut self, row: u32, col: u32, value: &str) {
-        self.cells[(col + row * self.cols) as usize] = value.to_string();
+    pub fn set_cell(&mut self, row: u32, col: u32, value: T) {
+        self.cells[(col + row * self.cols) as usize] = value;
     }
 }
 
-impl std::fmt::Display for PuzzleGrid {
+impl<T: std::fmt::Display> std::fmt::Display for PuzzleGrid<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let cell_span: usize = 5;
         let col_sep = "|";
@@ -123,12 +296,587 @@ impl std::fmt::Display for PuzzleGrid {
     }
 }
 
-pub type PuzzleDaemon = Vec<String>;
+pub type PuzzleDaemon = Vec<Code>;
+
+/// Progress of a puzzle which was partially played before being scanned
+#[derive(Debug, Clone, PartialEq)]
+pub struct PuzzleState {
+    /// Codes already in the buffer
+    pub buffer: Vec<Code>,
+    /// Cells already selected, which cannot be selected again
+    pub used_cells: Vec<CellCoord>,
+    /// Row or column where the next code is selected
+    pub active: PuzzleMove,
+}
+
+impl Default for PuzzleState {
+    fn default() -> Self {
+        PuzzleState {
+            buffer: Vec::new(),
+            used_cells: Vec::new(),
+            active: PuzzleMove::Row(0),
+        }
+    }
+}
+
+impl PuzzleState {
+    /// Check if any code was selected
+    pub fn is_started(&self) -> bool {
+        !self.buffer.is_empty() || !self.used_cells.is_empty()
+    }
+}
+
+impl std::fmt::Display for PuzzleState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let buffer: Vec<String> = self.buffer.iter().map(Code::to_string).collect();
+        write!(
+            f,
+            "buffer [{}], {} used cells, next code from ",
+            buffer.join(" "),
+            self.used_cells.len()
+        )?;
+        match self.active {
+            PuzzleMove::None => write!(f, "any line"),
+            PuzzleMove::Row(row) => write!(f, "row {}", row + 1),
+            PuzzleMove::Column(col) => write!(f, "column {}", col + 1),
+        }
+    }
+}
+
+/// Known daemon types, recognized from the daemon name shown next to its sequence
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DaemonType {
+    #[default]
+    Unknown,
+    DatamineV1,
+    DatamineV2,
+    DatamineV3,
+    DatamineCopyMalware,
+    IcePick,
+    MassVulnerability,
+    CameraShutdown,
+    FriendlyTurrets,
+    TurretShutdown,
+    OpticsJammer,
+    WeaponsJammer,
+}
+
+impl DaemonType {
+    /// All the known daemon types
+    pub const KNOWN: [DaemonType; 11] = [
+        DaemonType::DatamineV1,
+        DaemonType::DatamineV2,
+        DaemonType::DatamineV3,
+        DaemonType::DatamineCopyMalware,
+        DaemonType::IcePick,
+        DaemonType::MassVulnerability,
+        DaemonType::CameraShutdown,
+        DaemonType::FriendlyTurrets,
+        DaemonType::TurretShutdown,
+        DaemonType::OpticsJammer,
+        DaemonType::WeaponsJammer,
+    ];
 
+    /// Identifier used in the settings and in the puzzle text format
+    pub fn id(self) -> &'static str {
+        match self {
+            DaemonType::Unknown => "unknown",
+            DaemonType::DatamineV1 => "datamine_v1",
+            DaemonType::DatamineV2 => "datamine_v2",
+            DaemonType::DatamineV3 => "datamine_v3",
+            DaemonType::DatamineCopyMalware => "datamine_copy_malware",
+            DaemonType::IcePick => "icepick",
+            DaemonType::MassVulnerability => "mass_vulnerability",
+            DaemonType::CameraShutdown => "camera_shutdown",
+            DaemonType::FriendlyTurrets => "friendly_turrets",
+            DaemonType::TurretShutdown => "turret_shutdown",
+            DaemonType::OpticsJammer => "optics_jammer",
+            DaemonType::WeaponsJammer => "weapons_jammer",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<DaemonType> {
+        DaemonType::KNOWN
+            .iter()
+            .copied()
+            .find(|daemon_type| daemon_type.id() == id)
+    }
+
+    /// Name of the daemon as shown in the english version of the game
+    pub fn name(self) -> &'static str {
+        match self {
+            DaemonType::Unknown => "Unknown daemon",
+            DaemonType::DatamineV1 => "Datamine V1",
+            DaemonType::DatamineV2 => "Datamine V2",
+            DaemonType::DatamineV3 => "Datamine V3",
+            DaemonType::DatamineCopyMalware => "Datamine: Copy Malware",
+            DaemonType::IcePick => "ICEpick",
+            DaemonType::MassVulnerability => "Mass Vulnerability",
+            DaemonType::CameraShutdown => "Camera Shutdown",
+            DaemonType::FriendlyTurrets => "Friendly Turrets",
+            DaemonType::TurretShutdown => "Turret Shutdown",
+            DaemonType::OpticsJammer => "Optics Jammer",
+            DaemonType::WeaponsJammer => "Weapons Jammer",
+        }
+    }
+
+    /// Reward for uploading the daemon
+    pub fn reward(self) -> &'static str {
+        match self {
+            DaemonType::Unknown => "unknown reward",
+            DaemonType::DatamineV1 => "small amount of eurodollars and crafting components",
+            DaemonType::DatamineV2 => "medium amount of eurodollars and crafting components",
+            DaemonType::DatamineV3 => "large amount of eurodollars and crafting components",
+            DaemonType::DatamineCopyMalware => "copy of the daemon infecting the shard",
+            DaemonType::IcePick => "lower RAM cost of quickhacks in the network",
+            DaemonType::MassVulnerability => "lower physical resistance of enemies in the network",
+            DaemonType::CameraShutdown => "cameras in the network are disabled",
+            DaemonType::FriendlyTurrets => "turrets in the network attack enemies",
+            DaemonType::TurretShutdown => "turrets in the network are disabled",
+            DaemonType::OpticsJammer => "lower detection of enemies in the network",
+            DaemonType::WeaponsJammer => "weapons of enemies in the network are jammed",
+        }
+    }
+
+    /// Weight of the daemon when not all daemons can be uploaded, higher is more valuable.
+    /// Quest daemons come first, then the rewards which scale with the daemon version.
+    pub fn priority(self) -> u32 {
+        match self {
+            DaemonType::Unknown => 1,
+            DaemonType::DatamineV1 => 1,
+            DaemonType::DatamineV2 => 2,
+            DaemonType::DatamineV3 => 3,
+            DaemonType::DatamineCopyMalware => 10,
+            DaemonType::IcePick | DaemonType::MassVulnerability => 3,
+            DaemonType::CameraShutdown
+            | DaemonType::FriendlyTurrets
+            | DaemonType::TurretShutdown
+            | DaemonType::OpticsJammer
+            | DaemonType::WeaponsJammer => 2,
+        }
+    }
+}
+
+impl std::fmt::Display for DaemonType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Puzzle {
     pub buffer_size: u32,
     pub grid: PuzzleGrid,
     pub daemons: Vec<PuzzleDaemon>,
+    /// Type of each daemon, in daemon order
+    pub daemon_types: Vec<DaemonType>,
+}
+
+/// Inconsistencies in the puzzle model which would make it unsolvable
+#[derive(Debug, Clone, PartialEq)]
+pub enum PuzzleError {
+    /// The number of cells does not match the grid size
+    GridSize { rows: u32, cols: u32, cells: usize },
+    /// The buffer cannot hold any code
+    EmptyBuffer,
+    /// The puzzle has no daemons to upload
+    NoDaemons,
+    /// The n-th daemon has no codes
+    EmptyDaemon { daemon: usize },
+    /// The n-th daemon cannot fit in the buffer
+    DaemonTooLong {
+        daemon: usize,
+        length: usize,
+        buffer_size: u32,
+    },
+    /// The n-th daemon contains a code which is neither in the buffer nor in the unused cells
+    UnreachableCode { daemon: usize, code: Code },
+}
+
+impl std::fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PuzzleError::GridSize { rows, cols, cells } => {
+                write!(f, "a {}x{} grid cannot have {} cells", rows, cols, cells)
+            }
+            PuzzleError::EmptyBuffer => write!(f, "buffer size must be greater than 0"),
+            PuzzleError::NoDaemons => write!(f, "puzzle has no daemons"),
+            PuzzleError::EmptyDaemon { daemon } => write!(f, "daemon #{} is empty", daemon + 1),
+            PuzzleError::DaemonTooLong {
+                daemon,
+                length,
+                buffer_size,
+            } => write!(
+                f,
+                "daemon #{} has {} codes, but buffer size is {}",
+                daemon + 1,
+                length,
+                buffer_size
+            ),
+            PuzzleError::UnreachableCode { daemon, code } => write!(
+                f,
+                "daemon #{} code \"{}\" is neither in the buffer nor in an unused cell",
+                daemon + 1,
+                code
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+impl Puzzle {
+    /// Create a puzzle, checking that daemons are not empty, fit in the buffer and only use grid codes
+    pub fn try_new(
+        buffer_size: u32,
+        grid: PuzzleGrid,
+        daemons: Vec<PuzzleDaemon>,
+    ) -> Result<Puzzle, PuzzleError> {
+        Self::try_with_buffer(buffer_size, grid, daemons, &[])
+    }
+
+    /// Create a partially played puzzle, whose `buffer` holds the codes of the used cells.
+    /// Daemon codes must be in the buffer or in the cells which are not used yet.
+    pub fn try_with_buffer(
+        buffer_size: u32,
+        grid: PuzzleGrid,
+        daemons: Vec<PuzzleDaemon>,
+        buffer: &[Code],
+    ) -> Result<Puzzle, PuzzleError> {
+        if grid.rows == 0 || grid.cols == 0 || grid.cells.len() != (grid.rows * grid.cols) as usize
+        {
+            return Err(PuzzleError::GridSize {
+                rows: grid.rows,
+                cols: grid.cols,
+                cells: grid.cells.len(),
+            });
+        }
+        if buffer_size == 0 {
+            return Err(PuzzleError::EmptyBuffer);
+        }
+        if daemons.is_empty() {
+            return Err(PuzzleError::NoDaemons);
+        }
+        for (n, daemon) in daemons.iter().enumerate() {
+            if daemon.is_empty() {
+                return Err(PuzzleError::EmptyDaemon { daemon: n });
+            }
+            if daemon.len() > buffer_size as usize {
+                return Err(PuzzleError::DaemonTooLong {
+                    daemon: n,
+                    length: daemon.len(),
+                    buffer_size,
+                });
+            }
+            let unreachable = daemon.iter().find(|&code| {
+                !buffer.contains(code) && !grid.cells.iter().any(|cell| cell == code)
+            });
+            if let Some(code) = unreachable {
+                return Err(PuzzleError::UnreachableCode {
+                    daemon: n,
+                    code: *code,
+                });
+            }
+        }
+        Ok(Puzzle {
+            buffer_size,
+            grid,
+            daemon_types: vec![DaemonType::Unknown; daemons.len()],
+            daemons,
+        })
+    }
+
+    /// Type of the n-th daemon, `Unknown` if it was not recognized
+    pub fn daemon_type(&self, daemon: usize) -> DaemonType {
+        self.daemon_types
+            .get(daemon)
+            .copied()
+            .unwrap_or(DaemonType::Unknown)
+    }
+}
+
+/// Text format of a puzzle, e.g.
+/// ```text
+/// buffer: 7
+/// grid:
+/// 1C 1C 55
+/// 55 E9 BD
+/// BD 1C 55
+/// daemons:
+/// 1C 55
+/// 55 BD 1C
+/// ```
+/// Blank lines, leading indentation and `#` comments are ignored, and codes are case insensitive.
+/// A comment with a daemon type id after a daemon sets its type, e.g. `1C 55 # datamine_v1`.
+impl std::fmt::Display for Puzzle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "buffer: {}", self.buffer_size)?;
+        writeln!(f, "grid:")?;
+        for row in 0..self.grid.rows {
+            writeln!(f, "{}", join(self.grid.row(row)))?;
+        }
+        writeln!(f, "daemons:")?;
+        for (n, daemon) in self.daemons.iter().enumerate() {
+            match self.daemon_type(n) {
+                DaemonType::Unknown => writeln!(f, "{}", join(daemon))?,
+                daemon_type => writeln!(f, "{} # {}", join(daemon), daemon_type.id())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Codes or cells separated by spaces
+fn join<T: std::fmt::Display>(items: impl IntoIterator<Item = T>) -> String {
+    items
+        .into_iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParsePuzzleErrorKind {
+    /// A required section header (`buffer:`, `grid:` or `daemons:`) is missing
+    MissingSection(&'static str),
+    /// A section header appears more than once
+    DuplicateSection(&'static str),
+    /// A line that does not belong to any section
+    UnexpectedLine,
+    /// An extra value after the buffer size
+    UnexpectedToken(String),
+    /// The buffer size is not a positive integer
+    InvalidBufferSize(String),
+    /// A grid row has a different number of cells than the first row
+    RaggedRow { expected: u32, found: u32 },
+    /// A section header with no content
+    EmptySection(&'static str),
+    /// A grid or daemon cell is not a valid code
+    InvalidCode(CodeError),
+    /// The puzzle is well formed, but inconsistent
+    InvalidPuzzle(PuzzleError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsePuzzleError {
+    /// 1-based line number of the error
+    pub line: usize,
+    /// 1-based column number of the error
+    pub column: usize,
+    pub kind: ParsePuzzleErrorKind,
+}
+
+impl std::fmt::Display for ParsePuzzleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ParsePuzzleErrorKind::*;
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            MissingSection(name) => write!(f, "missing \"{}:\" section", name),
+            DuplicateSection(name) => write!(f, "duplicate \"{}:\" section", name),
+            UnexpectedLine => write!(f, "expected a section header before this line"),
+            UnexpectedToken(text) => write!(f, "unexpected \"{}\"", text),
+            InvalidBufferSize(text) => write!(f, "invalid buffer size \"{}\"", text),
+            RaggedRow { expected, found } => {
+                write!(
+                    f,
+                    "expected {} cells in grid row, found {}",
+                    expected, found
+                )
+            }
+            EmptySection(name) => write!(f, "\"{}:\" section is empty", name),
+            InvalidCode(error) => write!(f, "{}", error),
+            InvalidPuzzle(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ParsePuzzleError {}
+
+/// Split a line in whitespace separated tokens, each paired with its 1-based column number
+fn tokenize(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in line
+        .char_indices()
+        .chain(std::iter::once((line.len(), ' ')))
+    {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                tokens.push((line[..s].chars().count() + 1, &line[s..i]));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    tokens
+}
+
+/// Parse a line of code or cell tokens
+fn parse_codes<T: std::str::FromStr<Err = CodeError>>(
+    line: usize,
+    tokens: &[(usize, &str)],
+) -> Result<Vec<T>, ParsePuzzleError> {
+    tokens
+        .iter()
+        .map(|&(column, text)| {
+            text.parse().map_err(|error| ParsePuzzleError {
+                line,
+                column,
+                kind: ParsePuzzleErrorKind::InvalidCode(error),
+            })
+        })
+        .collect()
+}
+
+impl std::str::FromStr for Puzzle {
+    type Err = ParsePuzzleError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        use ParsePuzzleErrorKind::*;
+
+        #[derive(Clone, Copy, PartialEq)]
+        enum Section {
+            Buffer,
+            Grid,
+            Daemons,
+        }
+        let error = |line: usize, column: usize, kind| ParsePuzzleError { line, column, kind };
+
+        let mut section: Option<Section> = None;
+        let mut buffer_size: Option<u32> = None;
+        let mut grid_rows: Option<(usize, Vec<Vec<Cell>>)> = None;
+        let mut daemons: Option<(usize, Vec<PuzzleDaemon>)> = None;
+        let mut daemon_lines: Vec<usize> = Vec::new();
+        let mut daemon_types: Vec<DaemonType> = Vec::new();
+        let mut last_line = 0;
+
+        for (index, raw_line) in text.lines().enumerate() {
+            let line_no = index + 1;
+            last_line = line_no;
+            // Strip comments
+            let mut parts = raw_line.splitn(2, '#');
+            let line = parts.next().unwrap_or("");
+            let comment = parts.next().unwrap_or("").trim();
+            let tokens = tokenize(line);
+            let (column, first) = match tokens.first() {
+                Some(&token) => token,
+                None => continue,
+            };
+
+            // Section headers, optionally followed by inline content (e.g. "buffer: 7")
+            let header = first.to_lowercase();
+            let header_name = header.strip_suffix(':').unwrap_or(&header);
+            let new_section = match header_name {
+                "buffer" => Some((Section::Buffer, "buffer")),
+                "grid" => Some((Section::Grid, "grid")),
+                "daemons" => Some((Section::Daemons, "daemons")),
+                _ => None,
+            };
+            let content = if let Some((new_section, name)) = new_section {
+                let duplicate = match new_section {
+                    Section::Buffer => buffer_size.is_some(),
+                    Section::Grid => grid_rows.is_some(),
+                    Section::Daemons => daemons.is_some(),
+                };
+                if duplicate {
+                    return Err(error(line_no, column, DuplicateSection(name)));
+                }
+                match new_section {
+                    Section::Grid => grid_rows = Some((line_no, Vec::new())),
+                    Section::Daemons => daemons = Some((line_no, Vec::new())),
+                    Section::Buffer => (),
+                }
+                section = Some(new_section);
+                &tokens[1..]
+            } else {
+                &tokens[..]
+            };
+            if content.is_empty() {
+                continue;
+            }
+
+            match section {
+                None => return Err(error(line_no, column, UnexpectedLine)),
+                Some(Section::Buffer) => {
+                    // Only a single value is allowed in the buffer section
+                    let extra_token = if buffer_size.is_some() {
+                        content.first()
+                    } else {
+                        content.get(1)
+                    };
+                    if let Some(&(column, token)) = extra_token {
+                        return Err(error(line_no, column, UnexpectedToken(token.to_string())));
+                    }
+                    let (column, value) = content[0];
+                    let size = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|&size| size > 0)
+                        .ok_or_else(|| {
+                            error(line_no, column, InvalidBufferSize(value.to_string()))
+                        })?;
+                    buffer_size = Some(size);
+                }
+                Some(Section::Grid) => {
+                    let row: Vec<Cell> = parse_codes(line_no, content)?;
+                    let rows = &mut grid_rows.as_mut().unwrap().1;
+                    if let Some(first_row) = rows.first() {
+                        if first_row.len() != row.len() {
+                            let (column, _) = content[first_row.len().min(row.len() - 1)];
+                            return Err(error(
+                                line_no,
+                                column,
+                                RaggedRow {
+                                    expected: first_row.len() as u32,
+                                    found: row.len() as u32,
+                                },
+                            ));
+                        }
+                    }
+                    rows.push(row);
+                }
+                Some(Section::Daemons) => {
+                    let daemon = parse_codes(line_no, content)?;
+                    daemons.as_mut().unwrap().1.push(daemon);
+                    daemon_lines.push(line_no);
+                    daemon_types.push(DaemonType::from_id(comment).unwrap_or_default());
+                }
+            }
+        }
+
+        let end = last_line + 1;
+        let buffer_size = buffer_size.ok_or_else(|| error(end, 1, MissingSection("buffer")))?;
+        let (grid_line, rows) = grid_rows.ok_or_else(|| error(end, 1, MissingSection("grid")))?;
+        if rows.is_empty() {
+            return Err(error(grid_line, 1, EmptySection("grid")));
+        }
+        let (daemons_line, daemons) =
+            daemons.ok_or_else(|| error(end, 1, MissingSection("daemons")))?;
+        if daemons.is_empty() {
+            return Err(error(daemons_line, 1, EmptySection("daemons")));
+        }
+
+        // Rows were checked to have the same length, so the cells always fill the grid
+        let grid = PuzzleGrid::try_from_cells(
+            rows.len() as u32,
+            rows[0].len() as u32,
+            rows.into_iter().flatten().collect(),
+        )
+        .map_err(|e| error(grid_line, 1, InvalidPuzzle(e)))?;
+        let mut puzzle = Puzzle::try_new(buffer_size, grid, daemons).map_err(|e| {
+            // Point to the offending line
+            let line = match e {
+                PuzzleError::EmptyDaemon { daemon }
+                | PuzzleError::DaemonTooLong { daemon, .. }
+                | PuzzleError::UnreachableCode { daemon, .. } => daemon_lines[daemon],
+                _ => grid_line,
+            };
+            error(line, 1, InvalidPuzzle(e))
+        })?;
+        puzzle.daemon_types = daemon_types;
+        Ok(puzzle)
+    }
 }
 
 #[cfg(test)]
@@ -138,7 +886,7 @@ mod tests {
     #[test]
     fn grid() {
         #[rustfmt::skip]
-        let grid = PuzzleGrid::from_cells(
+        let grid: PuzzleGrid<String> = PuzzleGrid::try_from_cells(
             4,
             3,
             vec![
@@ -147,10 +895,223 @@ mod tests {
                 "6", "7", "8",
                 "9", "10", "11"
             ],
-        );
+        ).unwrap();
 
         assert_eq!(grid.row(1), ["3", "4", "5"]);
         assert_eq!(grid.col(1), ["1", "4", "7", "10"]);
         assert_eq!(grid.get_cell(2, 1), "7");
     }
+
+    #[test]
+    fn code() {
+        let code: Code = "1C".parse().unwrap();
+        assert_eq!(code, Code::new(0x1C));
+        assert_eq!("e9".parse::<Code>().unwrap().to_string(), "E9");
+        assert_eq!(format!("|{:^5}|", code), "| 1C  |");
+        assert_eq!(code, "1C");
+
+        assert_eq!(
+            "1CC".parse::<Code>(),
+            Err(CodeError::InvalidLength("1CC".to_string()))
+        );
+        assert_eq!(
+            "+1".parse::<Code>(),
+            Err(CodeError::InvalidHex("+1".to_string()))
+        );
+        assert_eq!(
+            "BO".parse::<Code>(),
+            Err(CodeError::InvalidHex("BO".to_string()))
+        );
+
+        // Used cells are not a code
+        assert_eq!(
+            "--".parse::<Code>(),
+            Err(CodeError::InvalidHex("--".to_string()))
+        );
+        assert_eq!("--".parse::<Cell>(), Ok(Cell::Used));
+        assert_eq!(Cell::Used.to_string(), "--");
+        assert_eq!("00".parse::<Cell>(), Ok(Cell::Code(Code::new(0))));
+        assert_eq!(Cell::Code(code), code);
+
+        let valid_codes = [Code::new(0x1C), Code::new(0x55)];
+        assert_eq!(code.validate(&valid_codes), Ok(code));
+        assert_eq!(
+            Code::new(0xFF).validate(&valid_codes),
+            Err(CodeError::NotAllowed(Code::new(0xFF)))
+        );
+    }
+
+    #[test]
+    fn puzzle_validation() {
+        let codes =
+            |codes: &[&str]| -> Vec<Code> { codes.iter().map(|c| c.parse().unwrap()).collect() };
+        let grid = || PuzzleGrid::try_from_cells(2, 2, codes(&["1C", "55", "55", "BD"])).unwrap();
+
+        assert_eq!(
+            PuzzleGrid::<Code>::try_from_cells(2, 2, codes(&["1C", "55", "55"])),
+            Err(PuzzleError::GridSize {
+                rows: 2,
+                cols: 2,
+                cells: 3
+            })
+        );
+        assert!(Puzzle::try_new(3, grid(), vec![codes(&["1C", "BD"])]).is_ok());
+        assert_eq!(
+            Puzzle::try_new(0, grid(), vec![codes(&["1C"])]),
+            Err(PuzzleError::EmptyBuffer)
+        );
+        assert_eq!(
+            Puzzle::try_new(3, grid(), vec![]),
+            Err(PuzzleError::NoDaemons)
+        );
+        assert_eq!(
+            Puzzle::try_new(3, grid(), vec![codes(&["1C"]), vec![]]),
+            Err(PuzzleError::EmptyDaemon { daemon: 1 })
+        );
+        assert_eq!(
+            Puzzle::try_new(3, grid(), vec![codes(&["1C", "55", "55", "BD"])]),
+            Err(PuzzleError::DaemonTooLong {
+                daemon: 0,
+                length: 4,
+                buffer_size: 3
+            })
+        );
+        assert_eq!(
+            Puzzle::try_new(3, grid(), vec![codes(&["1C", "E9"])]),
+            Err(PuzzleError::UnreachableCode {
+                daemon: 0,
+                code: Code::new(0xE9)
+            })
+        );
+        // The code of a used cell is only reachable from the buffer
+        let cells: Vec<Cell> = ["1C", "--", "55", "BD"]
+            .iter()
+            .map(|c| c.parse().unwrap())
+            .collect();
+        let used_grid = PuzzleGrid::try_from_cells(2, 2, cells).unwrap();
+        assert_eq!(
+            Puzzle::try_new(3, used_grid.clone(), vec![codes(&["1C", "E9"])]),
+            Err(PuzzleError::UnreachableCode {
+                daemon: 0,
+                code: Code::new(0xE9)
+            })
+        );
+        let played =
+            Puzzle::try_with_buffer(3, used_grid, vec![codes(&["1C", "E9"])], &codes(&["E9"]));
+        assert!(played.is_ok());
+        assert_eq!(
+            Puzzle::try_with_buffer(
+                3,
+                grid(),
+                vec![codes(&["1C"]), codes(&["FF"])],
+                &codes(&["E9"])
+            ),
+            Err(PuzzleError::UnreachableCode {
+                daemon: 1,
+                code: Code::new(0xFF)
+            })
+        );
+    }
+
+    #[test]
+    fn puzzle_from_str() {
+        let text = "
+            # Pasted from chat
+            buffer: 7
+            grid:
+              1c 1C 55
+              55 E9 BD
+              BD 1C 55
+
+            daemons:
+              1C 55
+              55 BD 1C
+        ";
+        let puzzle: Puzzle = text.parse().unwrap();
+        assert_eq!(puzzle.buffer_size, 7);
+        assert_eq!(puzzle.grid.rows, 3);
+        assert_eq!(puzzle.grid.cols, 3);
+        assert_eq!(puzzle.grid.row(0), ["1C", "1C", "55"]);
+        assert_eq!(
+            puzzle.daemons,
+            vec![vec!["1C", "55"], vec!["55", "BD", "1C"]]
+        );
+
+        // Display output parses back to the same puzzle
+        assert_eq!(puzzle.to_string().parse::<Puzzle>().unwrap(), puzzle);
+        assert_eq!(
+            puzzle.to_string(),
+            "buffer: 7\ngrid:\n1C 1C 55\n55 E9 BD\nBD 1C 55\ndaemons:\n1C 55\n55 BD 1C\n"
+        );
+    }
+
+    #[test]
+    fn puzzle_daemon_types() {
+        let text = "buffer: 4\ngrid:\n1C 55\n55 BD\ndaemons:\n1C 55 # datamine_v3\n55 BD # note\n";
+        let puzzle: Puzzle = text.parse().unwrap();
+        assert_eq!(
+            puzzle.daemon_types,
+            vec![DaemonType::DatamineV3, DaemonType::Unknown]
+        );
+        assert_eq!(puzzle.daemon_type(2), DaemonType::Unknown);
+        assert_eq!(
+            puzzle.to_string(),
+            "buffer: 4\ngrid:\n1C 55\n55 BD\ndaemons:\n1C 55 # datamine_v3\n55 BD\n"
+        );
+        assert_eq!(puzzle.to_string().parse::<Puzzle>().unwrap(), puzzle);
+
+        for daemon_type in DaemonType::KNOWN {
+            assert_eq!(DaemonType::from_id(daemon_type.id()), Some(daemon_type));
+        }
+        assert_eq!(DaemonType::from_id("unknown"), None);
+    }
+
+    #[test]
+    fn puzzle_from_str_errors() {
+        let error = "buffer: 7\ngrid:\n1C 55\n55 BD 1C\ndaemons:\n1C\n"
+            .parse::<Puzzle>()
+            .unwrap_err();
+        assert_eq!((error.line, error.column), (4, 7));
+        assert_eq!(
+            error.kind,
+            ParsePuzzleErrorKind::RaggedRow {
+                expected: 2,
+                found: 3
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "line 4, column 7: expected 2 cells in grid row, found 3"
+        );
+
+        let error = "buffer: x\n".parse::<Puzzle>().unwrap_err();
+        assert_eq!((error.line, error.column), (1, 9));
+        assert_eq!(
+            error.kind,
+            ParsePuzzleErrorKind::InvalidBufferSize("x".to_string())
+        );
+
+        let error = "buffer: 4\ngrid:\n1C ZZ\n".parse::<Puzzle>().unwrap_err();
+        assert_eq!((error.line, error.column), (3, 4));
+        assert_eq!(
+            error.kind,
+            ParsePuzzleErrorKind::InvalidCode(CodeError::InvalidHex("ZZ".to_string()))
+        );
+
+        let error = "buffer: 2\ngrid:\n1C 55\n55 1C\ndaemons:\n1C\n55 1C 55\n"
+            .parse::<Puzzle>()
+            .unwrap_err();
+        assert_eq!(error.line, 7);
+        assert_eq!(
+            error.to_string(),
+            "line 7, column 1: daemon #2 has 3 codes, but buffer size is 2"
+        );
+
+        let error = "1C 55\n".parse::<Puzzle>().unwrap_err();
+        assert_eq!(error.kind, ParsePuzzleErrorKind::UnexpectedLine);
+
+        let error = "buffer: 4\ngrid:\n1C\n".parse::<Puzzle>().unwrap_err();
+        assert_eq!((error.line, error.column), (4, 1));
+        assert_eq!(error.kind, ParsePuzzleErrorKind::MissingSection("daemons"));
+    }
 }
@@ -0,0 +1,219 @@
+use std::thread;
+use std::time::Duration;
+
+use opencv::core as cv;
+
+use crate::configuration::settings;
+use crate::render;
+use crate::scanner::{ChangeDetector, DebugDump, Scanner};
+use crate::screenshot::FrameSource;
+use crate::solver;
+use crate::types::{CellCoord, PuzzleGrid};
+
+/// Shows the solution of the scanned puzzle to the player, such as the overlay window
+pub(crate) trait SolutionView {
+    /// Screen area covered by the view once shown, which is left out of change detection
+    fn area(&self) -> Option<cv::Rect>;
+
+    /// Show the solution steps grid over the code matrix `area` of the frame, marking the cells
+    /// which may have been misread
+    fn show(&mut self, grid: &PuzzleGrid<String>, area: cv::Rect, uncertain: &[CellCoord]);
+
+    fn hide(&mut self);
+}
+
+/// View of platforms without overlay, the solution is only printed
+pub(crate) struct PrintedView;
+
+impl SolutionView for PrintedView {
+    fn area(&self) -> Option<cv::Rect> {
+        None
+    }
+
+    fn show(&mut self, _grid: &PuzzleGrid<String>, _area: cv::Rect, _uncertain: &[CellCoord]) {}
+
+    fn hide(&mut self) {}
+}
+
+/// Delays of the watch loop, from the `watch` settings
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct WatchIntervals {
+    /// Delay before the next frame while the panel does not change
+    pub poll: Duration,
+    /// Delay before the next frame after a failed capture or scan
+    pub retry: Duration,
+}
+
+impl WatchIntervals {
+    pub(crate) fn from_settings() -> WatchIntervals {
+        let settings = settings();
+        let cfg = &settings.watch;
+        WatchIntervals {
+            poll: Duration::from_millis(cfg.poll_interval_ms),
+            retry: Duration::from_millis(cfg.retry_interval_ms),
+        }
+    }
+}
+
+/// Number of frames watched until the source ended
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct WatchSummary {
+    pub frames: usize,
+    pub scans: usize,
+    pub failed_scans: usize,
+}
+
+/// Watch the frames of the source and show the solution of each new puzzle, until the source ends.
+/// Frames are only scanned when the breach panel changes, and the view is hidden as soon as it
+/// does. `debug_dump` gives the debug dump of a scan from its name.
+pub(crate) fn watch(
+    source: &mut dyn FrameSource,
+    scanner: &mut Scanner,
+    view: &mut dyn SolutionView,
+    intervals: &WatchIntervals,
+    debug_dump: &dyn Fn(&str) -> DebugDump,
+) -> WatchSummary {
+    let mut detector = ChangeDetector::new();
+    let mut summary = WatchSummary::default();
+
+    loop {
+        let screen = match source.next_frame() {
+            Ok(Some(screen)) => screen,
+            Ok(None) => return summary,
+            Err(error) => {
+                println!("Capture failed: {}", error);
+                view.hide();
+                detector.reset();
+                thread::sleep(intervals.retry);
+                continue;
+            }
+        };
+        summary.frames += 1;
+        match detector.changed(&screen) {
+            Ok(false) => {
+                thread::sleep(intervals.poll);
+                continue;
+            }
+            Ok(true) => (),
+            Err(error) => println!("Change detection failed: {}", error),
+        }
+        // The solution shown is outdated as soon as the panel changes
+        view.hide();
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let mut debug = debug_dump(&format!("scan-{}-{}", timestamp, summary.frames));
+        summary.scans += 1;
+        match scanner.scan_debug(&screen, &mut debug) {
+            Ok(result) => {
+                let puzzle = &result.puzzle;
+                if result.state.is_started() {
+                    println!("Puzzle in progress: {}", result.state);
+                }
+                for warning in result.warnings() {
+                    println!("Warning: {}", warning);
+                }
+                let solver = solver::BreachSolver::with_state(puzzle, &result.state);
+                if let Some(solution) = solver.solve(solver::SolverSearchMethod::Priority) {
+                    let colors = render::colors_supported();
+                    println!("{}", render::render_solution(puzzle, &solution, colors));
+                    let grid = solver.to_grid(&solution);
+                    view.show(&grid, result.grid_area, &result.uncertain_cells());
+                } else {
+                    println!("No solution found");
+                }
+                // The view is drawn on top of the panel, it must not count as a change
+                detector.ignore(view.area());
+                if let Err(error) = detector.watch(&screen, &result.panel) {
+                    println!("Change detection failed: {}", error);
+                    detector.reset();
+                }
+                thread::sleep(intervals.poll);
+            }
+            Err(error) => {
+                println!("Scan failed: {}", error);
+                summary.failed_scans += 1;
+                detector.reset();
+                thread::sleep(intervals.retry);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opencv::imgcodecs::{imread, imwrite, ImreadModes};
+    use opencv::prelude::*;
+
+    use super::*;
+    use crate::configuration::tests::load_default_settings;
+    use crate::screenshot::DirectorySource;
+
+    /// View recording what it was asked to do
+    #[derive(Default)]
+    struct RecordingView {
+        events: Vec<String>,
+        shown: Option<cv::Rect>,
+    }
+
+    impl SolutionView for RecordingView {
+        fn area(&self) -> Option<cv::Rect> {
+            self.shown
+        }
+
+        fn show(&mut self, grid: &PuzzleGrid<String>, area: cv::Rect, _uncertain: &[CellCoord]) {
+            self.shown = Some(area);
+            self.events
+                .push(format!("show {}x{}", grid.rows, grid.cols));
+        }
+
+        fn hide(&mut self) {
+            self.events.push("hide".to_string());
+        }
+    }
+
+    #[test]
+    fn watch_replayed_frames() {
+        load_default_settings();
+        // A puzzle staying on screen, another puzzle, then the puzzle closed
+        let dir = tempfile::tempdir().unwrap();
+        let frames = [
+            "test/test_6x6.png",
+            "test/test_6x6.png",
+            "test/test_5x5.jpg",
+        ];
+        for (n, file) in frames.iter().enumerate() {
+            let frame = imread(file, ImreadModes::IMREAD_UNCHANGED as i32).unwrap();
+            let path = dir.path().join(format!("frame{}.png", n + 1));
+            imwrite(&path.to_string_lossy(), &frame, &cv::Vector::new()).unwrap();
+        }
+        let closed =
+            Mat::new_rows_cols_with_default(1080, 1920, cv::CV_8UC3, cv::Scalar::all(0.0)).unwrap();
+        let path = dir.path().join("frame4.png");
+        imwrite(&path.to_string_lossy(), &closed, &cv::Vector::new()).unwrap();
+
+        let mut source = DirectorySource::new(dir.path()).unwrap();
+        let mut scanner = Scanner::new().unwrap();
+        let mut view = RecordingView::default();
+        let intervals = WatchIntervals {
+            poll: Duration::ZERO,
+            retry: Duration::ZERO,
+        };
+        let summary = watch(&mut source, &mut scanner, &mut view, &intervals, &|_| {
+            DebugDump::default()
+        });
+        assert_eq!(
+            summary,
+            WatchSummary {
+                frames: 4,
+                scans: 3,
+                failed_scans: 1
+            }
+        );
+        assert_eq!(
+            view.events,
+            ["hide", "show 6x6", "hide", "show 5x5", "hide"]
+        );
+    }
+}
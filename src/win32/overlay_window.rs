@@ -74,6 +74,23 @@ impl OverlayWindow {
         self.show();
     }
 
+    /// Move and resize the window, when placed elsewhere
+    fn place(&mut self, x: i32, y: i32, width: i32, height: i32) -> Result<(), String> {
+        if (self.x, self.y, self.width, self.height) == (x, y, width, height) {
+            return Ok(());
+        }
+        let flags = SWP_NOACTIVATE;
+        let result = unsafe { SetWindowPos(self.hwnd, HWND_TOPMOST, x, y, width, height, flags) };
+        if !result.as_bool() {
+            return Err(Error::from_win32().message().to_string());
+        }
+        self.x = x;
+        self.y = y;
+        self.width = width;
+        self.height = height;
+        Ok(())
+    }
+
     pub fn load_bitmap(&mut self, bitmap: &[u8]) -> Result<(), String> {
         let painter = OverlayWindowPainter::new_from_bitmap(self.hwnd, bitmap)?;
         let mut window = self.get_window_mut();
@@ -208,18 +225,24 @@ impl Paintable for OverlayWindowPainter {
     }
 }
 
+/// Bitmap to show, with the desktop area of the window
+struct OverlayImage {
+    rect: RECT,
+    bitmap_bytes: Vec<u8>,
+}
+
 #[derive(Clone)]
 pub struct OverlayController {
     hwnd: Arc<AtomicIsize>,
     is_visible: Arc<AtomicBool>,
-    tx: mpsc::Sender<Vec<u8>>,
+    tx: mpsc::Sender<OverlayImage>,
 }
 
 impl OverlayController {
     pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
         let hwnd = Arc::new(AtomicIsize::new(0));
         let is_visible = Arc::new(AtomicBool::new(false));
-        let (tx, rx) = mpsc::channel::<Vec<u8>>();
+        let (tx, rx) = mpsc::channel::<OverlayImage>();
 
         let controller = Self { hwnd, tx, is_visible };
         let controller_clone = controller.clone();
@@ -227,8 +250,11 @@ impl OverlayController {
             let mut overlay = OverlayWindow::new(x, y, width, height, "Overlay");
             controller_clone.hwnd.store(overlay.hwnd.0, Ordering::Release);
             loop {
-                let bitmap_bytes = rx.recv().unwrap();
-                overlay.load_bitmap(&bitmap_bytes).unwrap();
+                let image = rx.recv().unwrap();
+                let rect = image.rect;
+                let (width, height) = (rect.right - rect.left, rect.bottom - rect.top);
+                overlay.place(rect.left, rect.top, width, height).unwrap();
+                overlay.load_bitmap(&image.bitmap_bytes).unwrap();
                 overlay.show();
                 controller_clone.is_visible.store(true, Ordering::Release);
                 // writeln!(std::io::stdout(), "#### SHOW ####").unwrap();
@@ -240,9 +266,19 @@ impl OverlayController {
         controller
     }
 
-    pub fn load(&self, bitmap_bytes: &[u8]) -> () {
+    /// Show the bitmap in the given desktop area
+    pub fn load(&self, x: i32, y: i32, width: i32, height: i32, bitmap_bytes: &[u8]) -> () {
         self.hide();
-        self.tx.send(bitmap_bytes.to_owned()).expect("Failed to load bitmap");
+        let image = OverlayImage {
+            rect: RECT {
+                left: x,
+                top: y,
+                right: x + width,
+                bottom: y + height,
+            },
+            bitmap_bytes: bitmap_bytes.to_owned(),
+        };
+        self.tx.send(image).expect("Failed to load bitmap");
     }
 
     pub fn break_run_thread(&self) {
@@ -281,7 +317,7 @@ mod tests {
     fn it_loads_bitmap_bytes() {
         let bitmap_bytes = std::fs::read(FILE_TEST_BMP).expect("Cannot read test bitmap file");
         let overlay = OverlayController::new(0, 0, 300, 300);
-        overlay.load(&bitmap_bytes);
+        overlay.load(0, 0, 300, 300, &bitmap_bytes);
 
         sleep(Duration::from_secs(3));
         overlay.quit();
@@ -291,13 +327,13 @@ mod tests {
     fn it_cycles_show_hide() {
         let bitmap_bytes = std::fs::read(FILE_TEST_BMP).expect("Cannot read test bitmap file");
         let overlay = OverlayController::new(0, 0, 300, 300);
-        overlay.load(&bitmap_bytes);
+        overlay.load(0, 0, 300, 300, &bitmap_bytes);
 
         sleep(Duration::from_secs(1));
         overlay.hide();
 
         sleep(Duration::from_secs(1));
-        overlay.load(&bitmap_bytes);
+        overlay.load(0, 0, 300, 300, &bitmap_bytes);
 
         sleep(Duration::from_secs(3));
         overlay.quit();